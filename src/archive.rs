@@ -0,0 +1,433 @@
+//! Module for files holding several named dictionaries and side tables.
+//!
+//! An archive starts with [`ARCHIVE_MAGIC`], a little-endian version (u16),
+//! 2 reserved bytes and the number of sections (u32). The table of contents
+//! follows, with an entry per section:
+//!
+//! | Size | Field                        |
+//! |------|------------------------------|
+//! | 1    | kind                         |
+//! | 1    | reserved, 0                  |
+//! | 2    | length of the name           |
+//! | 8    | offset of the section        |
+//! | 8    | length of the section        |
+//! | 4    | CRC-32 of the section        |
+//! | *    | name in UTF-8                |
+//!
+//! and the CRC-32 of the entries (u32). Sections start at offsets aligned to
+//! 8 bytes. A dictionary section is in the format of
+//! [`DoubleArrayTrie::save_with_header`], so it can also be read with
+//! [`DoubleArrayTrie::open_with_header`] at the offset of the section.
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+
+use crate::{
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    header::{self, ByteOrder, HEADER_SIZE},
+};
+
+/// Bytes which identify an archive.
+pub const ARCHIVE_MAGIC: [u8; 8] = *b"DARTSARC";
+/// Version of the archive format written by this crate.
+pub const ARCHIVE_VERSION: u16 = 1;
+
+const PREFIX_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 24;
+const ALIGNMENT: u64 = 8;
+
+/// Kind of the data in a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    /// A dictionary with its header.
+    Dictionary,
+    /// Arbitrary bytes, e.g. a table of strings indexed by values.
+    Table,
+}
+
+/// Entry of the table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Name of the section, unique in the archive.
+    pub name: String,
+    /// Kind of the data.
+    pub kind: SectionKind,
+    /// Number of bytes from the beginning of the file.
+    pub offset: u64,
+    /// Number of bytes of the section.
+    pub len: u64,
+    /// CRC-32 of the bytes of the section.
+    pub checksum: u32,
+}
+
+enum Data<'a> {
+    Dictionary(&'a DoubleArrayTrie, u32),
+    Table(&'a [u8]),
+}
+
+impl Data<'_> {
+    fn write<W: Write>(&self, out: &mut W) -> Result<()> {
+        match *self {
+            Data::Dictionary(dic, flags) => dic.write_with_header(out, flags).map(|_| ()),
+            Data::Table(bytes) => out.write_all(bytes).map_err(Error::from),
+        }
+    }
+}
+
+/// Writer which collects sections and writes them in a single file.
+///
+/// ```ignore
+/// use darts::archive::ArchiveWriter;
+///
+/// let mut writer = ArchiveWriter::new();
+/// writer.add_dictionary("words", &words, 0);
+/// writer.add_table("labels", &labels);
+/// writer.write("path/to/archive")?;
+/// ```
+#[derive(Default)]
+pub struct ArchiveWriter<'a> {
+    sections: Vec<(String, Data<'a>)>,
+}
+
+impl<'a> ArchiveWriter<'a> {
+    /// Constructs an empty writer.
+    pub fn new() -> ArchiveWriter<'a> {
+        ArchiveWriter::default()
+    }
+
+    /// Adds a dictionary, with the flags of its header.
+    pub fn add_dictionary(&mut self, name: &str, dic: &'a DoubleArrayTrie, flags: u32) {
+        self.sections
+            .push((name.to_owned(), Data::Dictionary(dic, flags)));
+    }
+
+    /// Adds a side table of arbitrary bytes.
+    pub fn add_table(&mut self, name: &str, bytes: &'a [u8]) {
+        self.sections.push((name.to_owned(), Data::Table(bytes)));
+    }
+
+    /// Writes the table of contents and the sections into the specified
+    /// file, in the order they were added. Fails if two sections have the
+    /// same name.
+    pub fn write<P: AsRef<Path>>(&self, file_name: P) -> Result<Vec<Section>> {
+        let mut sections: Vec<Section> = Vec::with_capacity(self.sections.len());
+        let mut toc_size = PREFIX_SIZE + 4;
+        for (name, data) in &self.sections {
+            if sections.iter().any(|section| section.name == *name) {
+                return Err(Error::InvalidFormat(format!(
+                    "duplicate section {:?}",
+                    name
+                )));
+            }
+            if name.len() > u16::MAX as usize {
+                return Err(Error::InvalidFormat(format!(
+                    "section name too long: {:?}",
+                    name
+                )));
+            }
+            let mut digest = Digest::default();
+            data.write(&mut digest)?;
+            sections.push(Section {
+                name: name.clone(),
+                kind: match data {
+                    Data::Dictionary(..) => SectionKind::Dictionary,
+                    Data::Table(_) => SectionKind::Table,
+                },
+                offset: 0,
+                len: digest.len,
+                checksum: digest.crc.finalize(),
+            });
+            toc_size += ENTRY_SIZE + name.len();
+        }
+        let mut offset = align(toc_size as u64);
+        for section in &mut sections {
+            section.offset = offset;
+            offset = align(offset + section.len);
+        }
+
+        let mut out = BufWriter::new(File::create(file_name)?);
+        out.write_all(&encode_toc(&sections))?;
+        let mut pos = toc_size as u64;
+        for (section, (_, data)) in sections.iter().zip(&self.sections) {
+            out.write_all(&vec![0; (section.offset - pos) as usize])?;
+            data.write(&mut out)?;
+            pos = section.offset + section.len;
+        }
+        out.flush()?;
+        Ok(sections)
+    }
+}
+
+/// Writer which only counts and hashes bytes.
+#[derive(Default)]
+struct Digest {
+    crc: crc32fast::Hasher,
+    len: u64,
+}
+
+impl Write for Digest {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.crc.update(buf);
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn align(offset: u64) -> u64 {
+    offset.div_ceil(ALIGNMENT) * ALIGNMENT
+}
+
+fn encode_toc(sections: &[Section]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&ARCHIVE_MAGIC);
+    bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&[0; 2]);
+    bytes.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    for section in sections {
+        bytes.push(match section.kind {
+            SectionKind::Dictionary => 0,
+            SectionKind::Table => 1,
+        });
+        bytes.push(0);
+        bytes.extend_from_slice(&(section.name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&section.offset.to_le_bytes());
+        bytes.extend_from_slice(&section.len.to_le_bytes());
+        bytes.extend_from_slice(&section.checksum.to_le_bytes());
+        bytes.extend_from_slice(section.name.as_bytes());
+    }
+    let checksum = crc32fast::hash(&bytes[PREFIX_SIZE..]);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+fn read_toc<R: Read>(reader: &mut R, file_len: u64) -> Result<Vec<Section>> {
+    let invalid = |what: &str| Error::InvalidFormat(what.to_owned());
+    let mut prefix = [0; PREFIX_SIZE];
+    reader
+        .read_exact(&mut prefix)
+        .map_err(|_| invalid("missing archive magic bytes"))?;
+    if prefix[0..8] != ARCHIVE_MAGIC {
+        return Err(invalid("missing archive magic bytes"));
+    }
+    let version = u16::from_le_bytes([prefix[8], prefix[9]]);
+    if version == 0 || version > ARCHIVE_VERSION {
+        return Err(Error::InvalidFormat(format!(
+            "unsupported archive version {}",
+            version
+        )));
+    }
+    let num_sections = u32::from_le_bytes(prefix[12..16].try_into().unwrap());
+
+    let truncated = |_| invalid("truncated table of contents");
+    let mut crc = crc32fast::Hasher::new();
+    let mut sections = Vec::new();
+    for _ in 0..num_sections {
+        let mut entry = [0; ENTRY_SIZE];
+        reader.read_exact(&mut entry).map_err(truncated)?;
+        let name_len = u16::from_le_bytes([entry[2], entry[3]]) as usize;
+        let mut name = vec![0; name_len];
+        reader.read_exact(&mut name).map_err(truncated)?;
+        crc.update(&entry);
+        crc.update(&name);
+
+        let section = Section {
+            name: String::from_utf8(name).map_err(|_| invalid("section name is not UTF-8"))?,
+            kind: match entry[0] {
+                0 => SectionKind::Dictionary,
+                1 => SectionKind::Table,
+                _ => return Err(invalid("unknown section kind")),
+            },
+            offset: u64::from_le_bytes(entry[4..12].try_into().unwrap()),
+            len: u64::from_le_bytes(entry[12..20].try_into().unwrap()),
+            checksum: u32::from_le_bytes(entry[20..24].try_into().unwrap()),
+        };
+        if section
+            .offset
+            .checked_add(section.len)
+            .is_none_or(|end| end > file_len)
+        {
+            return Err(Error::InvalidFormat(format!(
+                "section {:?} is out of the file",
+                section.name
+            )));
+        }
+        // Units of mapped dictionaries are read in place.
+        if !section.offset.is_multiple_of(ALIGNMENT) {
+            return Err(Error::InvalidFormat(format!(
+                "section {:?} is not aligned",
+                section.name
+            )));
+        }
+        sections.push(section);
+    }
+    let mut checksum = [0; 4];
+    reader.read_exact(&mut checksum).map_err(truncated)?;
+    let expected = u32::from_le_bytes(checksum);
+    let actual = crc.finalize();
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+    Ok(sections)
+}
+
+enum Source {
+    File(PathBuf),
+    Mapped(Arc<Mmap>),
+}
+
+/// Archive opened for reading its sections by name.
+///
+/// ```ignore
+/// use darts::archive::Archive;
+///
+/// let archive = Archive::map("path/to/archive")?;
+/// let words = archive.dictionary("words")?;
+/// let labels = archive.table("labels")?;
+/// ```
+pub struct Archive {
+    source: Source,
+    sections: Vec<Section>,
+    verify: bool,
+}
+
+impl Archive {
+    /// Reads the table of contents of the specified file. Sections are read
+    /// from the file when they are requested.
+    pub fn open<P: AsRef<Path>>(file_name: P) -> Result<Archive> {
+        let path = file_name.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let file_len = file.metadata()?.len();
+        let sections = read_toc(&mut BufReader::new(file), file_len)?;
+        Ok(Archive {
+            source: Source::File(path),
+            sections,
+            verify: true,
+        })
+    }
+
+    /// Maps the specified file into memory and reads its table of contents.
+    /// Dictionaries then refer to the mapped units instead of copying them.
+    ///
+    /// The file must not be modified while it is mapped.
+    pub fn map<P: AsRef<Path>>(file_name: P) -> Result<Archive> {
+        let file = File::open(file_name)?;
+        let map = unsafe { Mmap::map(&file)? };
+        let sections = read_toc(&mut &map[..], map.len() as u64)?;
+        Ok(Archive {
+            source: Source::Mapped(Arc::new(map)),
+            sections,
+            verify: true,
+        })
+    }
+
+    /// Sets whether sections are checked against their checksums when they
+    /// are requested, which is the default. Checking a mapped section reads
+    /// all of its pages.
+    pub fn verify(mut self, verify: bool) -> Archive {
+        self.verify = verify;
+        self
+    }
+
+    /// Returns the table of contents.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Returns the entry of the section with the given name.
+    pub fn section(&self, name: &str) -> Result<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.name == name)
+            .ok_or_else(|| Error::SectionNotFound(name.to_owned()))
+    }
+
+    fn section_of_kind(&self, name: &str, kind: SectionKind) -> Result<&Section> {
+        let section = self.section(name)?;
+        if section.kind != kind {
+            return Err(Error::InvalidFormat(format!(
+                "section {:?} is not a {:?}",
+                name, kind
+            )));
+        }
+        Ok(section)
+    }
+
+    /// Returns the dictionary of the given section.
+    pub fn dictionary(&self, name: &str) -> Result<DoubleArrayTrie> {
+        let section = self.section_of_kind(name, SectionKind::Dictionary)?;
+        // The checksum of the section covers the header and the array, so
+        // the checksum in the header is not checked again.
+        let bytes = self.section_bytes(section)?;
+        match &self.source {
+            Source::File(_) => {
+                // The array must not go beyond the section.
+                let mut dic = DoubleArrayTrie::new();
+                dic.read_section(&mut &bytes[..], false)?;
+                Ok(dic)
+            }
+            Source::Mapped(map) => {
+                let header = header::Header::from_bytes(&bytes)?;
+                if HEADER_SIZE as u64 + header.payload_size() > section.len {
+                    return Err(Error::InvalidFormat(format!(
+                        "truncated section {:?}",
+                        name
+                    )));
+                }
+                if header.byte_order != ByteOrder::NATIVE {
+                    let units = &bytes[HEADER_SIZE..HEADER_SIZE + header.payload_size() as usize];
+                    return DoubleArrayTrie::from_units(header.byte_order.decode(units));
+                }
+                let offset = section.offset as usize + HEADER_SIZE;
+                DoubleArrayTrie::from_mapped(map.clone(), offset, header.num_units as usize)
+            }
+        }
+    }
+
+    /// Returns the bytes of the given side table, borrowed from the memory
+    /// map if the archive is mapped.
+    pub fn table(&self, name: &str) -> Result<Cow<'_, [u8]>> {
+        let section = self.section_of_kind(name, SectionKind::Table)?;
+        self.section_bytes(section)
+    }
+
+    /// Returns the bytes of a section, checked against its checksum if
+    /// `verify` is set.
+    fn section_bytes(&self, section: &Section) -> Result<Cow<'_, [u8]>> {
+        let bytes = match &self.source {
+            Source::File(path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(section.offset))?;
+                let mut bytes = vec![0; section.len as usize];
+                file.read_exact(&mut bytes)?;
+                Cow::Owned(bytes)
+            }
+            Source::Mapped(map) => Cow::Borrowed(self.mapped_bytes(map, section)),
+        };
+        if self.verify {
+            let actual = crc32fast::hash(&bytes);
+            if actual != section.checksum {
+                return Err(Error::ChecksumMismatch {
+                    expected: section.checksum,
+                    actual,
+                });
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn mapped_bytes<'a>(&self, map: &'a Mmap, section: &Section) -> &'a [u8] {
+        &map[section.offset as usize..(section.offset + section.len) as usize]
+    }
+}
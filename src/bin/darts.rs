@@ -0,0 +1,195 @@
+//! Command-line tool for building and querying dictionaries.
+
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    process,
+};
+
+use darts_clone_rs::{builder::BuildReport, darts::DoubleArrayTrie, error, text::TextFormat};
+
+const USAGE: &str = "\
+Usage: darts <command> [arguments]
+
+Commands:
+  build [--keys] <input> <dic>
+                             Builds a dictionary from lines of `key<TAB>value`,
+                             or of `key` with --keys, in which case each key gets
+                             its index in key order.
+  lookup <dic> [query...]    Prints the value of each query, or -1.
+  prefix <dic> [query...]    Prints the value and length of each key which is a
+                             prefix of the query.
+  longest <dic> [query...]   Prints the value and length of the longest key which
+                             is a prefix of the query, or -1 and 0.
+  dump <dic>                 Prints all key-value pairs in key order.
+  stats <dic>                Prints the size in bytes, the number of keys and
+                             units, the fill ratio, the depth and the shape of
+                             the dictionary.
+  verify [--keys] <dic> [input]
+                             Checks that every key of the dictionary, or of the
+                             input if given, is found with its value.
+
+Queries are read from the standard input, one per line, if none is given.";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["build", input, dic] => build(input, dic, &TextFormat::tsv()),
+        ["build", "--keys", input, dic] => build(input, dic, &TextFormat::lines()),
+        ["lookup", dic, ref queries @ ..] => query(dic, queries, lookup),
+        ["prefix", dic, ref queries @ ..] => query(dic, queries, prefix),
+        ["longest", dic, ref queries @ ..] => query(dic, queries, longest),
+        ["dump", dic] => dump(dic),
+        ["stats", dic] => stats(dic),
+        ["verify", dic] => verify(dic, None),
+        ["verify", dic, input] => verify(dic, Some((input, &TextFormat::tsv()))),
+        ["verify", "--keys", dic, input] => verify(dic, Some((input, &TextFormat::lines()))),
+        ["-h" | "--help" | "help"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("darts: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Builds a dictionary from `input`, reporting parse errors with the name of
+/// the input.
+fn read_dic(input: &str, format: &TextFormat) -> Result<(DoubleArrayTrie, BuildReport)> {
+    let reader = BufReader::new(File::open(input).map_err(|err| format!("{}: {}", input, err))?);
+    let mut trie = DoubleArrayTrie::new();
+    let report = trie
+        .build_from_reader(reader, format)
+        .map_err(|err| match err {
+            error::Error::Parse { line, message } => format!("{}:{}: {}", input, line, message),
+            err => format!("{}: {}", input, err),
+        })?;
+    Ok((trie, report))
+}
+
+fn open(dic: &str) -> Result<DoubleArrayTrie> {
+    let mut trie = DoubleArrayTrie::new();
+    trie.open_any(dic, 0)
+        .map_err(|err| format!("{}: failed to open dictionary: {}", dic, err))?;
+    Ok(trie)
+}
+
+fn build(input: &str, dic: &str, format: &TextFormat) -> Result<()> {
+    let (trie, report) = read_dic(input, format)?;
+    trie.save(dic, "wb", 0)
+        .map_err(|_| format!("{}: failed to save dictionary", dic))?;
+    println!(
+        "keys: {}, duplicates: {}, units: {}, bytes: {}",
+        report.num_keys,
+        report.num_duplicates,
+        trie.size(),
+        trie.total_size()
+    );
+    Ok(())
+}
+
+fn query(dic: &str, queries: &[&str], search: fn(&DoubleArrayTrie, &str) -> String) -> Result<()> {
+    let trie = open(dic)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    if queries.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            writeln!(out, "{}\t{}", line, search(&trie, &line))?;
+        }
+    } else {
+        for query in queries {
+            writeln!(out, "{}\t{}", query, search(&trie, query))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn lookup(trie: &DoubleArrayTrie, query: &str) -> String {
+    // Keys can not contain a null character.
+    if query.is_empty() || query.contains('\0') {
+        return "-1".to_owned();
+    }
+    trie.exact_match_search(query, query.len(), 0).to_string()
+}
+
+fn prefix(trie: &DoubleArrayTrie, query: &str) -> String {
+    let query = until_null(query);
+    if query.is_empty() {
+        return String::new();
+    }
+    // A query of n bytes has at most n prefixes.
+    trie.common_prefix_search(query, query.len(), query.len(), 0)
+        .iter()
+        .map(|result| format!("{}:{}", result.value, result.length))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn longest(trie: &DoubleArrayTrie, query: &str) -> String {
+    let query = until_null(query);
+    if query.is_empty() {
+        return "-1\t0".to_owned();
+    }
+    let result = trie.common_longest_prefix_search_pair(query, query.len(), 0);
+    format!("{}\t{}", result.value, result.length)
+}
+
+/// Cuts `query` at its first null character, which no key can match.
+fn until_null(query: &str) -> &str {
+    query.split('\0').next().unwrap()
+}
+
+fn dump(dic: &str) -> Result<()> {
+    let trie = open(dic)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    for (key, value) in trie.iter() {
+        out.write_all(&key)?;
+        writeln!(out, "\t{}", value)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn stats(dic: &str) -> Result<()> {
+    let trie = open(dic)?;
+    println!("bytes: {}", trie.total_size());
+    println!("{}", trie.stats());
+    Ok(())
+}
+
+fn verify(dic: &str, input: Option<(&str, &TextFormat)>) -> Result<()> {
+    let trie = open(dic)?;
+    // The input is built as by `build`, so only the first value of a key
+    // counts.
+    let expected = match input {
+        Some((input, format)) => Some(read_dic(input, format)?.0),
+        None => None,
+    };
+    let mut num_keys = 0;
+    let mut num_errors = 0;
+    for (key, value) in expected.as_ref().unwrap_or(&trie).iter() {
+        let found = trie.exact_match_search_bytes(&key, key.len(), 0);
+        if found != value {
+            let key = String::from_utf8_lossy(&key);
+            eprintln!("{}: expected {}, found {}", key, value, found);
+            num_errors += 1;
+        }
+        num_keys += 1;
+    }
+    if num_errors > 0 {
+        return Err(format!("{} of {} keys failed", num_errors, num_keys).into());
+    }
+    println!("ok: {} keys", num_keys);
+    Ok(())
+}
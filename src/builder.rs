@@ -0,0 +1,243 @@
+//! Module for building dictionaries from key-value pairs.
+
+use crate::{
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+};
+
+/// Checks that a key-value pair can be stored in a dictionary, i.e. the key
+/// is not empty nor contains a null character and the value is not negative.
+pub(crate) fn check_pair(index: usize, key: &[u8], value: i32) -> Result<()> {
+    if key.is_empty() || key.contains(&0) {
+        return Err(Error::InvalidKey {
+            index,
+            key: key.to_vec(),
+        });
+    }
+    if value < 0 {
+        return Err(Error::NegativeValue { index, value });
+    }
+    Ok(())
+}
+
+/// Decides what happens to the values of a key given more than once.
+#[derive(Default)]
+pub enum DuplicatePolicy {
+    /// Keeps the value given first, as [`DoubleArrayTrie::build`] does.
+    #[default]
+    KeepFirst,
+    /// Keeps the value given last.
+    KeepLast,
+    /// Fails with [`Error::DuplicateKey`].
+    Error,
+    /// Combines the values in the order they were given. The function is
+    /// called with the value combined so far and the next value.
+    Merge(Box<dyn FnMut(i32, i32) -> i32>),
+}
+
+/// Summary of a dictionary construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildReport {
+    /// Number of distinct keys stored in the dictionary.
+    pub num_keys: usize,
+    /// Number of key-value pairs dropped or merged because their key was
+    /// already given.
+    pub num_duplicates: usize,
+}
+
+/// Builder which accepts key-value pairs in any order.
+///
+/// Keys are sorted by their raw bytes, which is the order Darts-clone
+/// expects, and duplicate keys are resolved with a [`DuplicatePolicy`].
+///
+/// ```ignore
+/// use darts::builder::{DuplicatePolicy, SortingBuilder};
+///
+/// let mut builder = SortingBuilder::new().duplicate_policy(DuplicatePolicy::KeepLast);
+/// builder.push("banana", 1);
+/// builder.push("apple", 2);
+/// builder.push("banana", 3);
+/// let (dic, report) = builder.build()?;
+/// assert_eq!(dic.exact_match_search("banana", 0, 0), 3);
+/// assert_eq!(report.num_duplicates, 1);
+/// ```
+#[derive(Default)]
+pub struct SortingBuilder {
+    pairs: Vec<(Vec<u8>, i32)>,
+    policy: DuplicatePolicy,
+}
+
+impl SortingBuilder {
+    /// Constructs an empty builder which keeps the first value of duplicate keys.
+    pub fn new() -> SortingBuilder {
+        SortingBuilder::default()
+    }
+
+    /// Sets the policy for keys given more than once.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> SortingBuilder {
+        self.policy = policy;
+        self
+    }
+
+    /// Adds a key-value pair. The value must not be negative.
+    pub fn push<K: AsRef<[u8]>>(&mut self, key: K, value: i32) {
+        self.pairs.push((key.as_ref().to_vec(), value));
+    }
+
+    /// Returns the number of key-value pairs added so far, duplicates included.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Tests whether no key-value pair was added.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Sorts the key-value pairs, resolves duplicate keys and constructs
+    /// a dictionary from them.
+    pub fn build(self) -> Result<(DoubleArrayTrie, BuildReport)> {
+        let SortingBuilder {
+            mut pairs,
+            mut policy,
+        } = self;
+        // The sort is stable, so the values of a key stay in the given order.
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let num_pairs = pairs.len();
+        let mut builder = TrieBuilder::new();
+        let mut pairs = pairs.into_iter().peekable();
+        while let Some((key, mut value)) = pairs.next() {
+            while let Some((_, next_value)) = pairs.next_if(|(next_key, _)| *next_key == key) {
+                match policy {
+                    DuplicatePolicy::KeepFirst => {}
+                    DuplicatePolicy::KeepLast => value = next_value,
+                    DuplicatePolicy::Error => return Err(Error::DuplicateKey(key)),
+                    DuplicatePolicy::Merge(ref mut merge) => value = merge(value, next_value),
+                }
+            }
+            builder.insert(key, value)?;
+        }
+
+        let report = BuildReport {
+            num_keys: builder.len(),
+            num_duplicates: num_pairs - builder.len(),
+        };
+        Ok((builder.finish()?, report))
+    }
+}
+
+impl<K: AsRef<[u8]>> Extend<(K, i32)> for SortingBuilder {
+    fn extend<I: IntoIterator<Item = (K, i32)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.push(key, value);
+        }
+    }
+}
+
+/// Builder which takes key-value pairs one by one in key order.
+///
+/// Unlike [`DoubleArrayTrie::build`], the keys are checked as they are
+/// inserted and are stored back to back in a single buffer, so that a wrong
+/// key order or a negative value is reported with its index instead of
+/// failing in Darts-clone.
+///
+/// ```ignore
+/// use darts::builder::TrieBuilder;
+///
+/// let mut builder = TrieBuilder::new();
+/// builder.insert("apple", 1)?;
+/// builder.extend([("banana", 2), ("cherry", 3)])?;
+/// let dic = builder.finish()?;
+/// assert_eq!(dic.exact_match_search("banana", 0, 0), 2);
+/// ```
+#[derive(Default)]
+pub struct TrieBuilder {
+    bytes: Vec<u8>,
+    ends: Vec<usize>,
+    values: Vec<i32>,
+}
+
+impl TrieBuilder {
+    /// Constructs an empty builder.
+    pub fn new() -> TrieBuilder {
+        TrieBuilder::default()
+    }
+
+    /// Constructs an empty builder with room for `num_keys` keys whose total
+    /// length is `num_bytes`.
+    pub fn with_capacity(num_keys: usize, num_bytes: usize) -> TrieBuilder {
+        TrieBuilder {
+            bytes: Vec::with_capacity(num_bytes),
+            ends: Vec::with_capacity(num_keys),
+            values: Vec::with_capacity(num_keys),
+        }
+    }
+
+    /// Adds a key-value pair. The key must be greater than the previous key in
+    /// byte order, must not be empty nor contain a null character, and the
+    /// value must not be negative. Nothing is added if the pair is rejected.
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: i32) -> Result<()> {
+        let key = key.as_ref();
+        let index = self.len();
+        check_pair(index, key, value)?;
+        if let Some(last) = self.last_key() {
+            if key == last {
+                return Err(Error::DuplicateKey(key.to_vec()));
+            }
+            if key < last {
+                return Err(Error::WrongKeyOrder {
+                    index,
+                    key: key.to_vec(),
+                });
+            }
+        }
+
+        self.bytes.extend_from_slice(key);
+        self.ends.push(self.bytes.len());
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Adds key-value pairs in order, stopping at the first rejected pair.
+    pub fn extend<I, K>(&mut self, iter: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, i32)>,
+        K: AsRef<[u8]>,
+    {
+        for (key, value) in iter {
+            self.insert(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of key-value pairs added so far.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Tests whether no key-value pair was added.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    fn key(&self, index: usize) -> &[u8] {
+        let begin = if index == 0 { 0 } else { self.ends[index - 1] };
+        &self.bytes[begin..self.ends[index]]
+    }
+
+    fn last_key(&self) -> Option<&[u8]> {
+        self.len().checked_sub(1).map(|index| self.key(index))
+    }
+
+    /// Constructs a dictionary from the added key-value pairs.
+    pub fn finish(self) -> Result<DoubleArrayTrie> {
+        let keys = (0..self.len())
+            .map(|index| self.key(index))
+            .collect::<Vec<_>>();
+        let mut dic = DoubleArrayTrie::new();
+        dic.build_bytes(&keys, Some(&self.values), None)
+            .map_err(|what| Error::Darts(what.to_owned()))?;
+        Ok(dic)
+    }
+}
@@ -0,0 +1,211 @@
+//! Module for compressed dictionaries.
+//!
+//! A compressed file starts with [`COMPRESSED_MAGIC`], followed by the
+//! identifier of the [`Codec`] in one byte and seven reserved bytes, then by
+//! the compressed bytes of a file written by
+//! [`DoubleArrayTrie::save_with_header`]. The header inside is verified as
+//! usual once the data is decompressed.
+//!
+//! The built-in codecs are enabled by the cargo features `zstd` and `lz4`.
+//! Other codecs can be used by implementing [`Codec`] and passing it to the
+//! `_with` variants of the loading functions.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+use crate::{
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    header::Header,
+};
+
+/// Bytes which identify a compressed file.
+pub const COMPRESSED_MAGIC: [u8; 8] = *b"DARTSCMP";
+/// Number of bytes before the compressed data.
+pub const COMPRESSED_PREFIX_SIZE: usize = 16;
+
+/// Identifier of [`Zstd`].
+pub const CODEC_ZSTD: u8 = 1;
+/// Identifier of [`Lz4`].
+pub const CODEC_LZ4: u8 = 2;
+
+/// Compression algorithm of a compressed file.
+///
+/// Identifiers below 128 are reserved for the codecs of this crate.
+pub trait Codec {
+    /// Returns the identifier stored in the file.
+    fn id(&self) -> u8;
+
+    /// Compresses `data` into `out`.
+    fn compress(&self, data: &[u8], out: &mut dyn Write) -> std::io::Result<()>;
+
+    /// Decompresses all of `input` into `out`.
+    fn decompress(&self, input: &mut dyn Read, out: &mut Vec<u8>) -> std::io::Result<()>;
+}
+
+/// Zstandard, enabled by the feature `zstd`.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zstd {
+    /// Compression level, from 1 to 22.
+    pub level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl Default for Zstd {
+    fn default() -> Zstd {
+        Zstd {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Codec for Zstd {
+    fn id(&self) -> u8 {
+        CODEC_ZSTD
+    }
+
+    fn compress(&self, data: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        zstd::stream::copy_encode(data, out, self.level)
+    }
+
+    fn decompress(&self, input: &mut dyn Read, out: &mut Vec<u8>) -> std::io::Result<()> {
+        zstd::stream::copy_decode(input, out)
+    }
+}
+
+/// LZ4 frames, enabled by the feature `lz4`.
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Codec for Lz4 {
+    fn id(&self) -> u8 {
+        CODEC_LZ4
+    }
+
+    fn compress(&self, data: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(out);
+        encoder.write_all(data)?;
+        encoder.finish().map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    fn decompress(&self, input: &mut dyn Read, out: &mut Vec<u8>) -> std::io::Result<()> {
+        lz4_flex::frame::FrameDecoder::new(input).read_to_end(out)?;
+        Ok(())
+    }
+}
+
+/// Returns the built-in codec with the given identifier, if its feature is
+/// enabled.
+pub fn codec_for(id: u8) -> Option<Box<dyn Codec>> {
+    match id {
+        #[cfg(feature = "zstd")]
+        CODEC_ZSTD => Some(Box::new(Zstd::default())),
+        #[cfg(feature = "lz4")]
+        CODEC_LZ4 => Some(Box::new(Lz4)),
+        _ => None,
+    }
+}
+
+/// Reads the prefix of a compressed file and returns the codec identifier.
+fn read_prefix<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut prefix = [0; COMPRESSED_PREFIX_SIZE];
+    reader
+        .read_exact(&mut prefix)
+        .map_err(|_| Error::InvalidFormat("missing magic bytes".to_owned()))?;
+    if prefix[..8] != COMPRESSED_MAGIC {
+        return Err(Error::InvalidFormat("missing magic bytes".to_owned()));
+    }
+    Ok(prefix[8])
+}
+
+impl DoubleArrayTrie {
+    /// Writes the dictionary as by [`DoubleArrayTrie::save_with_header`],
+    /// compressed with `codec`.
+    ///
+    /// ```ignore
+    /// use darts::{compress::Zstd, darts::DoubleArrayTrie};
+    ///
+    /// dic.save_compressed("path/to/dict.zst", &Zstd::default(), 0)?;
+    /// let header = dic_copy.open_compressed("path/to/dict.zst")?;
+    /// ```
+    pub fn save_compressed(
+        &self,
+        file_name: &str,
+        codec: &dyn Codec,
+        flags: u32,
+    ) -> Result<Header> {
+        let mut out = BufWriter::new(File::create(file_name)?);
+        let header = self.write_compressed(&mut out, codec, flags)?;
+        out.flush()?;
+        Ok(header)
+    }
+
+    /// [`DoubleArrayTrie::save_compressed`] but writes to `out`.
+    pub fn write_compressed<W: Write>(
+        &self,
+        out: &mut W,
+        codec: &dyn Codec,
+        flags: u32,
+    ) -> Result<Header> {
+        let mut data = Vec::new();
+        let header = self.write_with_header(&mut data, flags)?;
+        let mut prefix = [0; COMPRESSED_PREFIX_SIZE];
+        prefix[..8].copy_from_slice(&COMPRESSED_MAGIC);
+        prefix[8] = codec.id();
+        out.write_all(&prefix)?;
+        codec.compress(&data, out)?;
+        Ok(header)
+    }
+
+    /// Reads a file written by [`DoubleArrayTrie::save_compressed`] with a
+    /// built-in codec. The units are decompressed into an array owned by the
+    /// dictionary.
+    pub fn open_compressed(&mut self, file_name: &str) -> Result<Header> {
+        self.read_compressed(&mut BufReader::new(File::open(file_name)?))
+    }
+
+    /// [`DoubleArrayTrie::open_compressed`] but decompresses with `codec`.
+    pub fn open_compressed_with(&mut self, file_name: &str, codec: &dyn Codec) -> Result<Header> {
+        self.read_compressed_with(&mut BufReader::new(File::open(file_name)?), codec)
+    }
+
+    /// [`DoubleArrayTrie::open_compressed`] but reads from `reader`, which
+    /// must be at the start of the compressed file.
+    pub fn read_compressed<R: Read>(&mut self, reader: &mut R) -> Result<Header> {
+        let id = read_prefix(reader)?;
+        let codec = codec_for(id)
+            .ok_or_else(|| Error::InvalidFormat(format!("unsupported codec {}", id)))?;
+        self.decompress_from(reader, codec.as_ref())
+    }
+
+    /// [`DoubleArrayTrie::read_compressed`] but decompresses with `codec`,
+    /// whose identifier must be the one in the file.
+    pub fn read_compressed_with<R: Read>(
+        &mut self,
+        reader: &mut R,
+        codec: &dyn Codec,
+    ) -> Result<Header> {
+        let id = read_prefix(reader)?;
+        if id != codec.id() {
+            return Err(Error::InvalidFormat(format!(
+                "codec {} does not match codec {} of the file",
+                codec.id(),
+                id
+            )));
+        }
+        self.decompress_from(reader, codec)
+    }
+
+    fn decompress_from<R: Read>(&mut self, reader: &mut R, codec: &dyn Codec) -> Result<Header> {
+        let mut data = Vec::new();
+        codec.decompress(reader, &mut data)?;
+        self.read_with_header(&mut data.as_slice())
+    }
+}
@@ -1,30 +1,91 @@
 //! Module for bindings to Darts-clone.
 
 use std::{
+    cell::RefCell,
     ffi::{c_void, CStr, CString},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    ops::Deref,
     ptr,
+    sync::Arc,
 };
 
 use libdarts_sys as raw;
+use memmap2::Mmap;
+
+use crate::{error::Error, header::ByteOrder, unit};
 
 /// Type of double array trie instance.
+///
+/// Searches only read the array of units, so a dictionary can be searched by
+/// several threads at once, e.g. through an `Arc<DoubleArrayTrie>`.
 pub struct DoubleArrayTrie {
     darts_t: raw::DartsT,
+    // Units which Darts-clone refers to without owning them.
+    storage: Storage,
+    // Message of the last error found before calling Darts-clone.
+    message: String,
+}
+
+// Darts-clone keeps no state tied to a thread, and searching only reads the
+// array of units, so a dictionary can be shared by threads. Everything which
+// modifies it takes `&mut self`.
+unsafe impl Send for DoubleArrayTrie {}
+unsafe impl Sync for DoubleArrayTrie {}
+
+/// Memory holding the array of units. Units set with `darts_set_array` are
+/// only kept alive here, Darts-clone being the one reading them.
+enum Storage {
+    None,
+    // Allocated by Darts-clone when building or opening.
+    Darts,
+    Owned(Vec<u32>),
+    Borrowed,
+    // Only keeps the map alive.
+    Mapped { _map: Arc<Mmap> },
+}
+
+/// Where the array of units of a dictionary lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ownership {
+    /// There is no array.
+    None,
+    /// The dictionary owns the array, e.g. after building or opening it.
+    Owned,
+    /// The array is borrowed, see [`DoubleArrayTrie::from_slice`] and
+    /// [`DoubleArrayTrie::set_array`].
+    Borrowed,
+    /// The array is in a memory-mapped file, e.g. of an archive.
+    Mapped,
 }
 
-/// Type of array of units.
-pub struct Array {
-    array: *const c_void,
+/// Dictionary searching units which it borrows, e.g. from an archive. It
+/// dereferences to a [`DoubleArrayTrie`].
+pub struct TrieRef<'a> {
+    dic: DoubleArrayTrie,
+    units: PhantomData<&'a [u32]>,
 }
 
-impl Array {
-    pub fn new() -> Array {
-        Array {
-            array: ptr::null_mut(),
+impl From<DoubleArrayTrie> for TrieRef<'_> {
+    /// Wraps a dictionary owning its units.
+    fn from(dic: DoubleArrayTrie) -> Self {
+        TrieRef {
+            dic,
+            units: PhantomData,
         }
     }
 }
 
+impl Deref for TrieRef<'_> {
+    type Target = DoubleArrayTrie;
+
+    fn deref(&self) -> &DoubleArrayTrie {
+        &self.dic
+    }
+}
+
 /// Enables applications to get the lengths of the
 /// matched keys in addition to the values.
 #[derive(Debug, PartialEq)]
@@ -33,43 +94,232 @@ pub struct ResultPairType {
     pub length: usize,
 }
 
+/// Largest value a dictionary can store. Darts-clone keeps 31 bits of each
+/// value, the last bit telling units of values from units of nodes.
+pub const MAX_VALUE: u32 = (1 << 31) - 1;
+
 /// Type of callback functions for reporting the progress of building a dictionary.
 pub type Progress = dyn FnMut(usize, usize) -> i32;
 
+/// Iterator over the key-value pairs of a dictionary in key order.
+///
+/// Keys are returned as bytes because a dictionary opened from a file is not
+/// guaranteed to hold valid UTF-8 keys.
+pub struct Iter<'a> {
+    units: &'a [u32],
+    key: Vec<u8>,
+    // Pairs of a node position and the next label to visit, 0 meaning that
+    // the leaf of the node has not been visited yet.
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a> Iter<'a> {
+    fn new(units: &'a [u32]) -> Iter<'a> {
+        let stack = if units.is_empty() { vec![] } else { vec![(0, 0)] };
+        Iter {
+            units,
+            key: Vec::new(),
+            stack,
+        }
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = (Vec<u8>, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((id, next_label)) = self.stack.last_mut() {
+            let id = *id;
+            if *next_label == 0 {
+                *next_label = 1;
+                if let Some(value) = unit::leaf_value(self.units, id) {
+                    return Some((self.key.clone(), value));
+                }
+                continue;
+            }
+            let found = (*next_label..=0xFF)
+                .find_map(|label| unit::child(self.units, id, label as u8).map(|c| (label, c)));
+            match found {
+                Some((label, child)) => {
+                    *next_label = label + 1;
+                    self.key.push(label as u8);
+                    self.stack.push((child, 0));
+                }
+                None => {
+                    self.stack.pop();
+                    self.key.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
 impl DoubleArrayTrie {
     /// Constructs an instance of double array trie.
     pub fn new() -> DoubleArrayTrie {
         DoubleArrayTrie {
             darts_t: unsafe { raw::darts_new() },
+            storage: Storage::None,
+            message: String::new(),
         }
     }
 
-    /// Calls [`DoubleArrayTrie::clear`] in order to free memory allocated to the
-    /// old array and then sets a new array. This function is useful to set a memory-
-    /// mapped array.
+    /// Constructs a dictionary owning `units`, which must be in the byte
+    /// order of the host. An empty array gives an empty dictionary.
     ///
-    /// It can also set the size of the new array but the size is not
-    /// used in search methods. So it works well even if the size is 0 or omitted.
-    /// Remember that [`DoubleArrayTrie::size`] and [`DoubleArrayTrie::total_size`]
-    /// returns 0 in such a case.
-    pub fn set_array(&mut self, array: &Array, size: usize) {
+    /// The array is checked so that searches can not read out of it, which
+    /// fails with [`Error::InvalidFormat`] if it is not made of whole blocks
+    /// of 256 units or if a node refers to children out of the array.
+    pub fn from_units(units: Vec<u32>) -> Result<DoubleArrayTrie, Error> {
+        if units.is_empty() {
+            return Ok(DoubleArrayTrie::new());
+        }
+        unit::validate(&units)?;
+        Ok(DoubleArrayTrie::from_valid_units(units))
+    }
+
+    /// [`DoubleArrayTrie::from_units`] without checking units built by this
+    /// crate.
+    pub(crate) fn from_valid_units(units: Vec<u32>) -> DoubleArrayTrie {
+        let mut dic = DoubleArrayTrie::new();
+        dic.set_owned_units(units);
+        dic
+    }
+
+    /// Moves the array of units out of the dictionary. It is copied unless
+    /// the dictionary owns it as a `Vec`, e.g. after
+    /// [`DoubleArrayTrie::from_units`].
+    pub fn into_units(mut self) -> Vec<u32> {
+        match std::mem::replace(&mut self.storage, Storage::None) {
+            Storage::Owned(units) => {
+                // Darts-clone must not refer to the units any longer.
+                self.clear();
+                units
+            }
+            _ => self.array().to_vec(),
+        }
+    }
+
+    /// Returns where the array of units lives.
+    pub fn ownership(&self) -> Ownership {
+        match self.storage {
+            Storage::None => Ownership::None,
+            Storage::Darts | Storage::Owned(_) => Ownership::Owned,
+            Storage::Borrowed => Ownership::Borrowed,
+            Storage::Mapped { .. } => Ownership::Mapped,
+        }
+    }
+
+    /// Replaces the array with units built on the Rust side.
+    pub(crate) fn set_owned_units(&mut self, units: Vec<u32>) {
+        unsafe {
+            raw::darts_set_array(self.darts_t, units.as_ptr() as *const c_void, units.len());
+        }
+        self.storage = Storage::Owned(units);
+    }
+
+    /// Constructs a dictionary searching `units` in place, without copying
+    /// them. The units are checked as by [`DoubleArrayTrie::from_units`].
+    pub fn from_slice(units: &[u32]) -> Result<TrieRef<'_>, Error> {
+        if !units.is_empty() {
+            unit::validate(units)?;
+        }
+        Ok(DoubleArrayTrie::from_valid_slice(units))
+    }
+
+    /// [`DoubleArrayTrie::from_slice`] without checking units which were
+    /// checked before.
+    pub(crate) fn from_valid_slice(units: &[u32]) -> TrieRef<'_> {
+        let mut dic = DoubleArrayTrie::new();
+        if !units.is_empty() {
+            dic.set_borrowed_units(units);
+        }
+        TrieRef {
+            dic,
+            units: PhantomData,
+        }
+    }
+
+    fn set_borrowed_units(&mut self, units: &[u32]) {
         unsafe {
-            raw::darts_set_array(self.darts_t, array.array, size);
+            raw::darts_set_array(self.darts_t, units.as_ptr() as *const c_void, units.len());
         }
+        self.storage = Storage::Borrowed;
     }
 
-    /// Returns a instance to the array of units.
-    pub fn array(&self) -> Array {
-        Array {
-            array: unsafe { raw::darts_array(self.darts_t) },
+    /// Constructs a dictionary from `num_units` units in a memory map,
+    /// starting at byte `offset`, which must be a multiple of 4.
+    /// The units are checked as by [`DoubleArrayTrie::from_units`].
+    pub(crate) fn from_mapped(
+        map: Arc<Mmap>,
+        offset: usize,
+        num_units: usize,
+    ) -> Result<DoubleArrayTrie, Error> {
+        if !offset.is_multiple_of(4)
+            || num_units
+                .checked_mul(4)
+                .and_then(|len| len.checked_add(offset))
+                .is_none_or(|end| end > map.len())
+        {
+            return Err(Error::InvalidFormat(
+                "units are not aligned or out of the map".to_owned(),
+            ));
         }
+        let mut dic = DoubleArrayTrie::new();
+        // Maps start at a page, so the units are aligned.
+        let units =
+            unsafe { std::slice::from_raw_parts(map.as_ptr().add(offset) as *const u32, num_units) };
+        unit::validate(units)?;
+        dic.set_borrowed_units(units);
+        dic.storage = Storage::Mapped { _map: map };
+        Ok(dic)
+    }
+
+    /// Calls [`DoubleArrayTrie::clear`] in order to free memory allocated to the
+    /// old array and then sets a new array, which is borrowed for as long as
+    /// the program runs, e.g. a `static` array. Arrays which live shorter are
+    /// searched with [`DoubleArrayTrie::from_slice`].
+    ///
+    /// The units are checked as by [`DoubleArrayTrie::from_units`], and the
+    /// old array is kept if they are invalid.
+    pub fn set_array(&mut self, units: &'static [u32]) -> Result<(), Error> {
+        unit::validate(units)?;
+        self.clear();
+        self.set_borrowed_units(units);
+        Ok(())
+    }
+
+    /// Returns the array of units. It is empty if no array was built or
+    /// opened.
+    pub fn array(&self) -> &[u32] {
+        let array = unsafe { raw::darts_array(self.darts_t) } as *const u32;
+        let size = self.size();
+        if array.is_null() || size == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(array, size) }
+    }
+
+    /// Returns an iterator over all the key-value pairs in key order. It
+    /// borrows the dictionary, which can not be cleared, built or opened
+    /// again until the iterator is dropped.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(self.array())
+    }
+
+    /// Tests whether both dictionaries hold the same keys with the same
+    /// values, however their arrays of units are laid out.
+    pub fn same_contents(&self, other: &DoubleArrayTrie) -> bool {
+        self.iter().eq(other.iter())
     }
 
     /// Frees memory allocated to units.
-    pub fn clear(&self) {
+    pub fn clear(&mut self) {
         unsafe {
             raw::darts_clear(self.darts_t);
         }
+        self.storage = Storage::None;
     }
 
     /// Returns the size of each unit.
@@ -77,13 +327,12 @@ impl DoubleArrayTrie {
         unsafe { raw::darts_unit_size(self.darts_t) }
     }
 
-    /// Returns the number of units. It can be 0 if [`DoubleArrayTrie::set_array`] is used.
+    /// Returns the number of units.
     pub fn size(&self) -> usize {
         unsafe { raw::darts_size(self.darts_t) }
     }
 
-    /// Returns the number of bytes allocated to the array of units.
-    /// It can be 0 if [`DoubleArrayTrie::set_array`] is used.
+    /// Returns the number of bytes of the array of units.
     pub fn total_size(&self) -> usize {
         unsafe { raw::darts_total_size(self.darts_t) }
     }
@@ -100,8 +349,12 @@ impl DoubleArrayTrie {
     /// the ith key has (i - 1) as its value.
     ///
     /// Note that the key-value pairs must be arranged in key order and the values
-    /// must not be negative. Also, if there are duplicate keys, only the first
-    /// pair will be stored in the resultant dictionary.
+    /// must be from 0 to [`MAX_VALUE`]. Also, if there are duplicate keys, only
+    /// the first pair will be stored in the resultant dictionary.
+    ///
+    /// It fails without calling Darts-clone if a value is negative, telling
+    /// its index, or if fewer than `num_keys` keys, lengths or values are
+    /// given.
     ///
     /// `progress_func` is a optional callback function. If it is not None,
     /// it will be called when building so that the caller can check the progress of
@@ -111,13 +364,36 @@ impl DoubleArrayTrie {
     /// this case, Darts-clone uses a Directed Acyclic Word Graph (DAWG) instead
     /// of a trie because a DAWG is likely to be more compact than a trie.
     pub fn build(
-        &self,
+        &mut self,
         num_keys: usize,
-        keys: &Vec<String>,
+        keys: &[String],
         lengths: Option<&[usize]>,
         values: Option<&[i32]>,
         progress_func: Option<Box<Progress>>,
     ) -> Result<(), &str> {
+        let counts = [
+            ("keys", Some(keys.len())),
+            ("lengths", lengths.map(<[_]>::len)),
+            ("values", values.map(<[_]>::len)),
+        ];
+        for (what, count) in counts {
+            if let Some(count) = count.filter(|&count| count < num_keys) {
+                self.message = Error::CountMismatch {
+                    what,
+                    count,
+                    num_keys,
+                }
+                .to_string();
+                return Err(&self.message);
+            }
+        }
+        let values = values.map(|values| &values[..num_keys]);
+        if let Some(index) = values.and_then(|values| values.iter().position(|&value| value < 0)) {
+            let value = values.unwrap()[index];
+            self.message = Error::NegativeValue { index, value }.to_string();
+            return Err(&self.message);
+        }
+
         let keys = keys
             .iter()
             .map(|key| CString::new(key.as_bytes()).unwrap())
@@ -129,28 +405,95 @@ impl DoubleArrayTrie {
         }
         c_keys.push(ptr::null());
 
+        self.build_raw(num_keys, &c_keys, lengths, values, progress_func)
+    }
+
+    /// [`DoubleArrayTrie::build`] but builds all the keys and takes a value
+    /// for each of them as `u32`. Values greater than [`MAX_VALUE`] can not
+    /// be stored, so they fail with [`Error::ValueOutOfRange`] instead of
+    /// being cut to 31 bits, and fewer lengths or values than keys fail with
+    /// [`Error::CountMismatch`].
+    pub fn build_u32(
+        &mut self,
+        keys: &[String],
+        lengths: Option<&[usize]>,
+        values: &[u32],
+        progress_func: Option<Box<Progress>>,
+    ) -> Result<(), Error> {
+        let num_keys = keys.len();
+        let counts = [("lengths", lengths.map(<[_]>::len)), ("values", Some(values.len()))];
+        for (what, count) in counts {
+            if let Some(count) = count.filter(|&count| count < num_keys) {
+                return Err(Error::CountMismatch {
+                    what,
+                    count,
+                    num_keys,
+                });
+            }
+        }
+        let values = values[..num_keys]
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                i32::try_from(value).map_err(|_| Error::ValueOutOfRange { index, value })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.build(num_keys, keys, lengths, Some(&values), progress_func)
+            .map_err(|what| Error::Darts(what.to_owned()))
+    }
+
+    /// [`DoubleArrayTrie::build`] but takes keys as byte strings, which are
+    /// passed to Darts-clone with their lengths instead of being copied.
+    pub(crate) fn build_bytes(
+        &mut self,
+        keys: &[&[u8]],
+        values: Option<&[i32]>,
+        progress_func: Option<Box<Progress>>,
+    ) -> Result<(), &str> {
+        let c_keys = keys
+            .iter()
+            .map(|key| key.as_ptr() as *const std::os::raw::c_char)
+            .collect::<Vec<_>>();
+        let lengths = keys.iter().map(|key| key.len()).collect::<Vec<_>>();
+
+        self.build_raw(keys.len(), &c_keys, Some(&lengths), values, progress_func)
+    }
+
+    fn build_raw(
+        &mut self,
+        num_keys: usize,
+        c_keys: &[*const std::os::raw::c_char],
+        lengths: Option<&[usize]>,
+        values: Option<&[i32]>,
+        progress_func: Option<Box<Progress>>,
+    ) -> Result<(), &str> {
         let c_lengths = match lengths {
-            Some(lengths) => &lengths[0],
+            Some(lengths) => lengths.as_ptr(),
             None => ptr::null(),
         };
 
         let c_values = match values {
-            Some(values) => &values[0],
+            Some(values) => values.as_ptr(),
             None => ptr::null(),
         };
 
-        static mut STORED_PROGRESS: Option<Box<Progress>> = None;
+        // Darts-clone calls back on the building thread, so each thread has
+        // its own callback.
+        thread_local! {
+            static STORED_PROGRESS: RefCell<Option<Box<Progress>>> = const { RefCell::new(None) };
+        }
 
         unsafe {
-            STORED_PROGRESS = progress_func;
+            STORED_PROGRESS.with(|stored| *stored.borrow_mut() = progress_func);
             let retval = raw::darts_build(
                 self.darts_t,
                 num_keys,
-                &c_keys[0],
+                c_keys.as_ptr(),
                 c_lengths,
                 c_values,
                 Some(progress_callback),
             );
+            STORED_PROGRESS.with(|stored| *stored.borrow_mut() = None);
             if retval != 0 {
                 let err = CStr::from_ptr(raw::darts_error(self.darts_t));
                 return Err(err.to_str().unwrap());
@@ -158,12 +501,13 @@ impl DoubleArrayTrie {
         }
 
         unsafe extern "C" fn progress_callback(current: usize, totols: usize) -> i32 {
-            match STORED_PROGRESS {
+            STORED_PROGRESS.with(|stored| match *stored.borrow_mut() {
                 Some(ref mut f) => f(current, totols),
                 None => 0,
-            }
+            })
         }
 
+        self.storage = Storage::Darts;
         Ok(())
     }
 
@@ -172,45 +516,127 @@ impl DoubleArrayTrie {
     /// from the file. `offset` specifies the number of bytes to be skipped before
     /// reading an array. `size` specifies the number of bytes to be read from the
     /// file. If the `size` is 0, the whole file will be read.
+    ///
+    /// The units are little-endian in the file, so they are swapped on
+    /// big-endian hosts. They are checked before they replace the old array,
+    /// which is kept if they refer to units out of the array.
     pub fn open(
-        &self,
+        &mut self,
         file_name: &str,
         mode: &str,
         offset: usize,
         size: usize,
     ) -> Result<(), &str> {
+        if ByteOrder::NATIVE != ByteOrder::Little {
+            return self.open_in(file_name, offset, size, ByteOrder::Little);
+        }
         let c_file_name = CString::new(file_name).unwrap();
         let c_mode = CString::new(mode).unwrap();
+        let mut dic = DoubleArrayTrie::new();
         unsafe {
             let retval = raw::darts_open(
-                self.darts_t,
+                dic.darts_t,
                 c_file_name.as_ptr(),
                 c_mode.as_ptr(),
                 offset,
                 size,
             );
             if retval != 0 {
-                let err = CStr::from_ptr(raw::darts_error(self.darts_t));
-                return Err(err.to_str().unwrap());
+                // Darts-clone does not report why a file could not be read.
+                let err = raw::darts_error(dic.darts_t);
+                if err.is_null() {
+                    return Err("Error opening file.");
+                }
+                self.message = CStr::from_ptr(err).to_string_lossy().into_owned();
+                return Err(&self.message);
             }
-            Ok(())
         }
+        dic.storage = Storage::Darts;
+        if let Err(err) = unit::validate(dic.array()) {
+            self.message = err.to_string();
+            return Err(&self.message);
+        }
+        *self = dic;
+        Ok(())
     }
 
     /// Writes the array of units into the specified file. `offset`
     /// specifies the number of bytes to be skipped before writing the array.
+    ///
+    /// The units are written in little-endian, swapping them on big-endian
+    /// hosts.
     pub fn save(&self, file_name: &str, mode: &str, offset: usize) -> Result<(), &str> {
-        let c_file_name = CString::new(file_name).unwrap();
-        let c_mode = CString::new(mode).unwrap();
-        unsafe {
-            let retval =
-                raw::darts_save(self.darts_t, c_file_name.as_ptr(), c_mode.as_ptr(), offset);
-            if retval != 0 {
-                let err = CStr::from_ptr(raw::darts_error(self.darts_t));
-                return Err(err.to_str().unwrap());
+        // Darts-clone records a failure to save in the instance, which must
+        // not be modified through a shared reference, so units are written
+        // on the Rust side.
+        self.save_in(file_name, mode, offset, ByteOrder::Little)
+    }
+
+    /// Reads an array of units stored in the given byte order, which
+    /// Darts-clone can not do if it differs from the byte order of the host.
+    /// The units are checked as by [`DoubleArrayTrie::from_units`].
+    pub(crate) fn open_in(
+        &mut self,
+        file_name: &str,
+        offset: usize,
+        size: usize,
+        byte_order: ByteOrder,
+    ) -> Result<(), &str> {
+        let read = || -> io::Result<Vec<u8>> {
+            let mut file = File::open(file_name)?;
+            file.seek(SeekFrom::Start(offset as u64))?;
+            let mut bytes = Vec::new();
+            if size == 0 {
+                file.read_to_end(&mut bytes)?;
+            } else {
+                bytes.resize(size, 0);
+                file.read_exact(&mut bytes)?;
             }
-            Ok(())
+            Ok(bytes)
+        };
+        let bytes = read().map_err(|_| "Error opening file.")?;
+        let num_units = bytes.len() / 4;
+        if num_units < 256 || num_units % 256 != 0 {
+            return Err("Error opening file.");
         }
+        match DoubleArrayTrie::from_units(byte_order.decode(&bytes[..num_units * 4])) {
+            Ok(dic) => *self = dic,
+            Err(err) => {
+                self.message = err.to_string();
+                return Err(&self.message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the array of units in the given byte order. `mode` is handled
+    /// as by `fopen`, i.e. "a" appends and "r" writes over an existing file.
+    pub(crate) fn save_in(
+        &self,
+        file_name: &str,
+        mode: &str,
+        offset: usize,
+        byte_order: ByteOrder,
+    ) -> Result<(), &str> {
+        let units = self.array();
+        if units.is_empty() {
+            return Err("Error saving file.");
+        }
+        let write = || -> io::Result<()> {
+            let mut options = OpenOptions::new();
+            match mode.chars().next() {
+                Some('a') => options.append(true).create(true),
+                Some('r') => options.write(true),
+                _ => options.write(true).create(true).truncate(true),
+            };
+            let mut file = BufWriter::new(options.open(file_name)?);
+            file.seek(SeekFrom::Start(offset as u64))?;
+            for &unit in units {
+                file.write_all(&byte_order.encode(unit))?;
+            }
+            file.flush()
+        };
+        write().map_err(|_| "Error saving file.")
     }
 
     /// Tests whether the given key exists or not, and if it exists,
@@ -251,6 +677,33 @@ impl DoubleArrayTrie {
         }
     }
 
+    /// [`DoubleArrayTrie::exact_match_search`] but returns the value as
+    /// `u32`, as given to [`DoubleArrayTrie::build_u32`], or [`None`] if the
+    /// key is not found.
+    pub fn exact_match_search_u32(
+        &self,
+        key: &str,
+        length: usize,
+        node_pos: usize,
+    ) -> Option<u32> {
+        if self.array().is_empty() {
+            return None;
+        }
+        u32::try_from(self.exact_match_search(key, length, node_pos)).ok()
+    }
+
+    /// [`DoubleArrayTrie::exact_match_search`] but takes the key as bytes,
+    /// which need not be UTF-8. A key containing a null character is not
+    /// found.
+    pub fn exact_match_search_bytes(&self, key: &[u8], length: usize, node_pos: usize) -> i32 {
+        match CString::new(key) {
+            Ok(c_key) => unsafe {
+                raw::darts_exact_match_search(self.darts_t, c_key.as_ptr(), length, node_pos)
+            },
+            Err(_) => -1,
+        }
+    }
+
     /// Searches for keys which match a prefix of the given string.
     /// If `length` is 0, `key` is handled as a string.
     /// The values and the lengths of at most `max_num_results` matched keys are
@@ -344,6 +797,7 @@ impl DoubleArrayTrie {
     /// associated with the final accept state. That is, this function returns the
     /// value associated with the given key if it exists. Note that this function
     /// updates `node_pos` and `key_pos` after each transition.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn traverse(
         &self,
         key: &str,
@@ -362,6 +816,56 @@ impl Default for DoubleArrayTrie {
     }
 }
 
+impl Clone for DoubleArrayTrie {
+    /// Copies the array of units into a dictionary which owns it.
+    fn clone(&self) -> Self {
+        let units = self.array();
+        if units.is_empty() {
+            return DoubleArrayTrie::new();
+        }
+        DoubleArrayTrie::from_valid_units(units.to_vec())
+    }
+}
+
+impl PartialEq for DoubleArrayTrie {
+    /// Tests whether the arrays of units are the same. Dictionaries of the
+    /// same keys built differently may not be equal, see
+    /// [`DoubleArrayTrie::same_contents`].
+    fn eq(&self, other: &Self) -> bool {
+        self.array() == other.array()
+    }
+}
+
+impl Eq for DoubleArrayTrie {}
+
+impl fmt::Debug for DoubleArrayTrie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NUM_SAMPLE_KEYS: usize = 5;
+
+        struct SampleKeys<'a>(&'a DoubleArrayTrie);
+
+        impl fmt::Debug for SampleKeys<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut list = f.debug_list();
+                let mut keys = self.0.iter().map(|(key, _)| key);
+                for key in keys.by_ref().take(NUM_SAMPLE_KEYS) {
+                    list.entry(&String::from_utf8_lossy(&key));
+                }
+                if keys.next().is_some() {
+                    list.entry(&format_args!(".."));
+                }
+                list.finish()
+            }
+        }
+
+        f.debug_struct("DoubleArrayTrie")
+            .field("size", &self.size())
+            .field("total_size", &self.total_size())
+            .field("keys", &SampleKeys(self))
+            .finish()
+    }
+}
+
 impl Drop for DoubleArrayTrie {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,279 @@
+//! Module for dictionaries edited after they are built.
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    mem,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    builder::{check_pair, TrieBuilder},
+    darts::{DoubleArrayTrie, ResultPairType},
+    error::{Error, Result},
+    unit,
+};
+
+/// Edits not yet built into a double array, a value inserting or updating
+/// the key and `None` deleting it.
+type Edits = BTreeMap<Vec<u8>, Option<i32>>;
+
+/// Rebuild running on another thread with the edits it applies.
+struct Rebuild {
+    edits: Arc<Edits>,
+    thread: JoinHandle<Result<DoubleArrayTrie>>,
+}
+
+/// Dictionary which accepts insertions, updates and deletions.
+///
+/// Edits are kept in a small map consulted before the double array, and
+/// once there are `threshold` of them they are built with the double array
+/// into a new one on another thread. Searches keep consulting the edits
+/// until the new array is installed by the next edit or by
+/// [`DeltaTrie::poll`].
+///
+/// ```ignore
+/// use darts::delta::DeltaTrie;
+///
+/// let mut dic = DeltaTrie::new(dic).threshold(1000);
+/// dic.insert("apple", 10)?;
+/// dic.remove("banana");
+/// assert_eq!(dic.exact_match_search("apple"), Some(10));
+/// assert_eq!(dic.exact_match_search("banana"), None);
+/// ```
+pub struct DeltaTrie {
+    base: Arc<DoubleArrayTrie>,
+    edits: Edits,
+    rebuild: Option<Rebuild>,
+    threshold: usize,
+    num_edits: usize,
+    error: Option<Error>,
+}
+
+impl DeltaTrie {
+    /// Constructs a dictionary holding the keys of `dic`, which is rebuilt
+    /// every 1024 edits.
+    pub fn new(dic: DoubleArrayTrie) -> DeltaTrie {
+        DeltaTrie {
+            base: Arc::new(dic),
+            edits: Edits::new(),
+            rebuild: None,
+            threshold: 1024,
+            num_edits: 0,
+            error: None,
+        }
+    }
+
+    /// Sets the number of pending edits which starts a rebuild.
+    pub fn threshold(mut self, threshold: usize) -> DeltaTrie {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Returns the double array the pending edits apply to.
+    pub fn base(&self) -> &DoubleArrayTrie {
+        &self.base
+    }
+
+    /// Returns the number of keys edited since the double array was built,
+    /// including the ones being built into a new one.
+    pub fn num_pending_edits(&self) -> usize {
+        let rebuilding = self
+            .rebuild
+            .as_ref()
+            .map_or(0, |rebuild| rebuild.edits.len());
+        self.edits.len() + rebuilding
+    }
+
+    /// Tests whether a rebuild is running.
+    pub fn is_rebuilding(&self) -> bool {
+        self.rebuild.is_some()
+    }
+
+    /// Inserts a key-value pair, or updates the value of the key. The key
+    /// must not be empty nor contain a null character, and the value must
+    /// not be negative. Errors report the number of edits made before.
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: i32) -> Result<()> {
+        let key = key.as_ref();
+        check_pair(self.num_edits, key, value)?;
+        self.edit(key.to_vec(), Some(value));
+        Ok(())
+    }
+
+    /// Deletes a key and returns its value, if it was found.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<i32> {
+        let key = key.as_ref();
+        let value = self.get(key)?;
+        self.edit(key.to_vec(), None);
+        Some(value)
+    }
+
+    fn edit(&mut self, key: Vec<u8>, value: Option<i32>) {
+        self.poll();
+        self.edits.insert(key, value);
+        self.num_edits += 1;
+        if self.rebuild.is_none() && self.edits.len() >= self.threshold {
+            let edits = Arc::new(mem::take(&mut self.edits));
+            let base = self.base.clone();
+            let thread = {
+                let edits = edits.clone();
+                thread::spawn(move || apply(&base, &edits))
+            };
+            self.rebuild = Some(Rebuild { edits, thread });
+        }
+    }
+
+    /// Installs the new double array if a rebuild has finished, and returns
+    /// whether it did. If the rebuild failed, its edits are pending again
+    /// and the error is kept for [`DeltaTrie::take_error`].
+    pub fn poll(&mut self) -> bool {
+        if !self
+            .rebuild
+            .as_ref()
+            .is_some_and(|rebuild| rebuild.thread.is_finished())
+        {
+            return false;
+        }
+        match self.finish_rebuild() {
+            Ok(()) => true,
+            Err(err) => {
+                self.error = Some(err);
+                false
+            }
+        }
+    }
+
+    /// Waits for the running rebuild, if any, and builds all the pending
+    /// edits into a new double array.
+    pub fn flush(&mut self) -> Result<()> {
+        self.finish_rebuild()?;
+        if !self.edits.is_empty() {
+            self.base = Arc::new(apply(&self.base, &self.edits)?);
+            self.edits.clear();
+        }
+        Ok(())
+    }
+
+    /// Returns the error of the last failed rebuild, if any, and forgets it.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Waits for the running rebuild, if any, and installs its array.
+    fn finish_rebuild(&mut self) -> Result<()> {
+        let Some(Rebuild { edits, thread }) = self.rebuild.take() else {
+            return Ok(());
+        };
+        let result = thread
+            .join()
+            .unwrap_or_else(|_| Err(Error::Darts("rebuild panicked".to_owned())));
+        match result {
+            Ok(dic) => {
+                self.base = Arc::new(dic);
+                Ok(())
+            }
+            Err(err) => {
+                // Edits made since the rebuild started are newer.
+                for (key, value) in Arc::unwrap_or_clone(edits) {
+                    self.edits.entry(key).or_insert(value);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the value of `key`, looking at the newest edits first.
+    fn get(&self, key: &[u8]) -> Option<i32> {
+        match self.edited(key) {
+            Some(value) => value,
+            None => unit::exact_value(self.base.array(), key),
+        }
+    }
+
+    /// Returns the last edit of `key`, if it was edited.
+    fn edited(&self, key: &[u8]) -> Option<Option<i32>> {
+        if let Some(&value) = self.edits.get(key) {
+            return Some(value);
+        }
+        let rebuild = self.rebuild.as_ref()?;
+        rebuild.edits.get(key).copied()
+    }
+
+    /// Returns the value of `key`, if it exists.
+    pub fn exact_match_search(&self, key: &str) -> Option<i32> {
+        self.get(key.as_bytes())
+    }
+
+    /// Searches for keys which match a prefix of `text` and returns their
+    /// values and lengths in ascending order of length.
+    pub fn common_prefix_search(&self, text: &str) -> Vec<ResultPairType> {
+        // Keys can not contain a null character.
+        let text = text.split('\0').next().unwrap();
+        let mut results = Vec::new();
+        if !text.is_empty() && !self.base.array().is_empty() {
+            results = self
+                .base
+                .common_prefix_search(text, text.len(), text.len(), 0);
+        }
+        if self.edits.is_empty() && self.rebuild.is_none() {
+            return results;
+        }
+
+        let mut base_results = results.into_iter().peekable();
+        let mut results = Vec::new();
+        for length in 1..=text.len() {
+            let base_value = base_results
+                .next_if(|result| result.length == length)
+                .map(|result| result.value);
+            let value = match self.edited(&text.as_bytes()[..length]) {
+                Some(value) => value,
+                None => base_value,
+            };
+            if let Some(value) = value {
+                results.push(ResultPairType { value, length });
+            }
+        }
+        results
+    }
+
+    /// Searches for the longest key which matches a prefix of `text` and
+    /// returns its value and length.
+    pub fn common_longest_prefix_search(&self, text: &str) -> Option<ResultPairType> {
+        self.common_prefix_search(text).pop()
+    }
+}
+
+/// Builds the key-value pairs of `dic` with `edits` applied.
+fn apply(dic: &DoubleArrayTrie, edits: &Edits) -> Result<DoubleArrayTrie> {
+    let mut builder = TrieBuilder::new();
+    let mut pairs = dic.iter().peekable();
+    let mut edits = edits.iter().peekable();
+    loop {
+        let order = match (pairs.peek(), edits.peek()) {
+            (Some((key, _)), Some((edited_key, _))) => key.cmp(edited_key),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            Ordering::Less => {
+                let (key, value) = pairs.next().unwrap();
+                builder.insert(key, value)?;
+            }
+            Ordering::Greater | Ordering::Equal => {
+                if order == Ordering::Equal {
+                    pairs.next();
+                }
+                let (key, value) = edits.next().unwrap();
+                if let Some(value) = value {
+                    builder.insert(key, *value)?;
+                }
+            }
+        }
+    }
+    if builder.is_empty() {
+        return Ok(DoubleArrayTrie::new());
+    }
+    builder.finish()
+}
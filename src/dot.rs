@@ -0,0 +1,106 @@
+//! Module for drawing dictionaries with Graphviz.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::{darts::DoubleArrayTrie, unit};
+
+impl DoubleArrayTrie {
+    /// Renders the whole dictionary in the DOT language of Graphviz.
+    ///
+    /// ```ignore
+    /// std::fs::write("dict.dot", dic.to_dot())?;
+    /// // dot -Tsvg dict.dot > dict.svg
+    /// ```
+    pub fn to_dot(&self) -> String {
+        self.to_dot_from(0, None)
+    }
+
+    /// Renders the nodes reachable from the node at `node_pos` in the DOT
+    /// language of Graphviz, down to `max_depth` transitions if given. Nodes
+    /// whose children are cut off by the limit are dashed.
+    ///
+    /// Nodes are named after their position, as used by
+    /// [`DoubleArrayTrie::traverse`], and accept states are drawn with two
+    /// circles and their value. Printable ASCII labels are drawn as they
+    /// are, other bytes as `\xNN`, except that the bytes of a UTF-8 character
+    /// along a path without branches are drawn as one transition.
+    pub fn to_dot_from(&self, node_pos: usize, max_depth: Option<usize>) -> String {
+        let units = self.array();
+        let mut dot = String::from("digraph trie {\n    node [shape=circle];\n");
+        if node_pos >= units.len() {
+            dot.push_str("}\n");
+            return dot;
+        }
+
+        // Nodes shared by a DAWG are drawn once.
+        let mut visited = HashSet::from([node_pos]);
+        let mut queue = VecDeque::from([(node_pos, 0)]);
+        while let Some((id, depth)) = queue.pop_front() {
+            let mut attributes = match unit::leaf_value(units, id) {
+                Some(value) => format!("label=\"{}: {}\", shape=doublecircle", id, value),
+                None => format!("label=\"{}\"", id),
+            };
+            let mut children = unit::children(units, id).peekable();
+            let is_cut = max_depth == Some(depth);
+            if is_cut && children.peek().is_some() {
+                attributes.push_str(", style=dashed");
+            }
+            writeln!(dot, "    n{} [{}];", id, attributes).unwrap();
+            if is_cut {
+                continue;
+            }
+
+            for (label, child) in children {
+                let (label, child) = utf8_transition(units, label, child)
+                    .unwrap_or_else(|| (escape_byte(label), child));
+                writeln!(dot, "    n{} -> n{} [label=\"{}\"];", id, child, label).unwrap();
+                if visited.insert(child) {
+                    queue.push_back((child, depth + 1));
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Follows the continuation bytes of a UTF-8 character whose first byte is
+/// `label`, through nodes with no leaf and a single child. Returns the
+/// character and the node after it.
+fn utf8_transition(units: &[u32], label: u8, mut child: usize) -> Option<(String, usize)> {
+    let len = match label {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let mut bytes = vec![label];
+    while bytes.len() < len {
+        if unit::leaf_value(units, child).is_some() {
+            return None;
+        }
+        let mut children = unit::children(units, child);
+        match (children.next(), children.next()) {
+            (Some((next_label, next)), None) if next_label & 0xC0 == 0x80 => {
+                bytes.push(next_label);
+                child = next;
+            }
+            _ => return None,
+        }
+    }
+    let text = String::from_utf8(bytes).ok()?;
+    Some((text, child))
+}
+
+/// Returns a byte as it is written in a DOT string.
+fn escape_byte(byte: u8) -> String {
+    match byte {
+        b'"' => "\\\"".to_owned(),
+        b'\\' => "\\\\".to_owned(),
+        0x20..=0x7E => (byte as char).to_string(),
+        _ => format!("\\\\x{:02X}", byte),
+    }
+}
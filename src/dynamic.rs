@@ -0,0 +1,379 @@
+//! Module for inserting keys into a built double array.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    builder::check_pair,
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    layout::{self, BLOCK_SIZE},
+    unit,
+};
+
+/// Offsets below it can be encoded, whatever their low byte.
+const NEAR_OFFSET: usize = 1 << 21;
+/// Maximum number of free units tried as the base of a node near it.
+const MAX_CANDIDATES: usize = 16 * BLOCK_SIZE;
+/// Number of last blocks where a base aligned with its node is looked for.
+const NUM_ALIGNED_BLOCKS: usize = 16;
+
+/// Double array which keys can be inserted into, one by one.
+///
+/// A new key takes free units next to the children of the node it leaves
+/// the array from. If one of them is taken, the children are moved with
+/// their leaf to units where they all fit, as in the classic dynamic double
+/// array, and the array grows by a block when no units fit. Children moved
+/// too far from their own children to encode the offset have those moved
+/// as well. The array stays in the format of Darts-clone, so it is searched
+/// as any other dictionary once [`DynamicTrie::into_trie`] is called.
+///
+/// Nodes shared by a DAWG are copied before a key is inserted below them,
+/// so that the keys sharing them are not changed.
+///
+/// ```ignore
+/// use darts::dynamic::DynamicTrie;
+///
+/// let mut dynamic = DynamicTrie::from_trie(dic);
+/// dynamic.insert("apple", 10)?;
+/// let dic = dynamic.into_trie();
+/// assert_eq!(dic.exact_match_search("apple", 0, 0), 10);
+/// ```
+pub struct DynamicTrie {
+    units: Vec<u32>,
+    /// Positions of the units which are not used.
+    free: BTreeSet<usize>,
+    /// Number of nodes whose children and leaf start at each position.
+    refs: HashMap<usize, usize>,
+    num_inserts: usize,
+}
+
+impl DynamicTrie {
+    /// Constructs an empty double array.
+    pub fn new() -> DynamicTrie {
+        let mut units = vec![0; BLOCK_SIZE];
+        // The root must not have an offset of 0, as in `layout::Arranger`.
+        units[0] = layout::node_unit(0, false, 1).unwrap();
+        DynamicTrie {
+            units,
+            free: (1..BLOCK_SIZE).collect(),
+            refs: HashMap::new(),
+            num_inserts: 0,
+        }
+    }
+
+    /// Constructs a double array from the array of `dic`, which is moved
+    /// without being copied if the dictionary owns it as a `Vec`.
+    pub fn from_trie(dic: DoubleArrayTrie) -> DynamicTrie {
+        let mut units = dic.into_units();
+        if units.is_empty() {
+            return DynamicTrie::new();
+        }
+
+        // Units which are not reached from the root are free. Darts-clone
+        // gives them labels which no node matches, so they are cleared for
+        // the nodes whose children are moved there.
+        let mut used = vec![false; units.len()];
+        let mut refs = HashMap::new();
+        let mut stack = vec![0];
+        used[0] = true;
+        while let Some(id) = stack.pop() {
+            let base = base(&units, id);
+            let mut has_base = unit::has_leaf(units[id]);
+            if has_base {
+                used[base] = true;
+            }
+            for (_, child) in unit::children(&units, id) {
+                has_base = true;
+                if !used[child] {
+                    used[child] = true;
+                    stack.push(child);
+                }
+            }
+            if has_base {
+                *refs.entry(base).or_insert(0) += 1;
+            }
+        }
+        for (unit, &used) in units.iter_mut().zip(&used) {
+            if !used {
+                *unit = 0;
+            }
+        }
+
+        let free = (0..units.len()).filter(|&id| !used[id]).collect();
+        DynamicTrie {
+            units,
+            free,
+            refs,
+            num_inserts: 0,
+        }
+    }
+
+    /// Returns the array of units.
+    pub fn array(&self) -> &[u32] {
+        &self.units
+    }
+
+    /// Constructs a dictionary searching the array of units.
+    pub fn into_trie(self) -> DoubleArrayTrie {
+        DoubleArrayTrie::from_valid_units(self.units)
+    }
+
+    /// Returns the value of `key`, if it exists.
+    pub fn exact_match_search(&self, key: &str) -> Option<i32> {
+        unit::exact_value(&self.units, key.as_bytes())
+    }
+
+    /// Inserts a key-value pair, or updates the value of the key and returns
+    /// the old value. The key must not be empty nor contain a null character,
+    /// and the value must not be negative. Errors report the number of
+    /// insertions made before.
+    ///
+    /// Fails with [`Error::TooManyUnits`] if the key needs units farther
+    /// than an offset can reach, in which case the keys inserted before are
+    /// kept but the array may hold nodes of the key which lead nowhere.
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: i32) -> Result<Option<i32>> {
+        let key = key.as_ref();
+        check_pair(self.num_inserts, key, value)?;
+        self.num_inserts += 1;
+
+        // Follows the key as far as the array has it.
+        let mut id = 0;
+        let mut depth = 0;
+        loop {
+            self.unshare(id)?;
+            if depth == key.len() {
+                break;
+            }
+            match unit::child(&self.units, id, key[depth]) {
+                Some(child) => id = child,
+                None => break,
+            }
+            depth += 1;
+        }
+
+        if depth == key.len() {
+            if unit::has_leaf(self.units[id]) {
+                let leaf = base(&self.units, id);
+                let old_value = unit::value(self.units[leaf]);
+                self.units[leaf] = layout::value_unit(value);
+                return Ok(Some(old_value));
+            }
+            let leaf = self.add_slot(id, 0)?;
+            self.units[leaf] = layout::value_unit(value);
+            self.set_node(id, true, base(&self.units, id))?;
+            return Ok(None);
+        }
+
+        // Adds a chain of nodes for the rest of the key.
+        let mut slot = self.add_slot(id, key[depth])?;
+        for (i, &label) in key.iter().enumerate().skip(depth) {
+            let next_label = key.get(i + 1).copied().unwrap_or(0);
+            let base = self.find_base(slot, &[next_label])?;
+            self.refs.insert(base, 1);
+            self.units[slot] = node_unit(label, next_label == 0, slot ^ base)?;
+            let next = base ^ next_label as usize;
+            self.use_unit(next);
+            if next_label == 0 {
+                self.units[next] = layout::value_unit(value);
+            }
+            slot = next;
+        }
+        Ok(None)
+    }
+
+    /// Copies the children of the node at `id` if other nodes share them.
+    fn unshare(&mut self, id: usize) -> Result<()> {
+        if self.has_base(id) && self.refs[&base(&self.units, id)] > 1 {
+            self.relocate(id, None)?;
+        }
+        Ok(())
+    }
+
+    /// Reserves the unit of the child labeled `label` of the node at `id`,
+    /// moving the other children if the unit is taken, and returns its
+    /// position. A label of 0 reserves the unit of the leaf.
+    fn add_slot(&mut self, id: usize, label: u8) -> Result<usize> {
+        let base = if !self.has_base(id) {
+            let base = self.find_base(id, &[label])?;
+            self.refs.insert(base, 1);
+            self.set_node(id, unit::has_leaf(self.units[id]), base)?;
+            base
+        } else {
+            let base = base(&self.units, id);
+            let slot = base ^ label as usize;
+            if self.free.contains(&slot) {
+                base
+            } else {
+                self.relocate(id, Some(label))?
+            }
+        };
+        let slot = base ^ label as usize;
+        self.use_unit(slot);
+        Ok(slot)
+    }
+
+    /// Moves the children and the leaf of the node at `id` to units where
+    /// they fit along with a child labeled `extra_label`, whose unit is left
+    /// free, and returns their new base. Children shared with other nodes
+    /// are copied instead.
+    fn relocate(&mut self, id: usize, extra_label: Option<u8>) -> Result<usize> {
+        let old_base = base(&self.units, id);
+        self.move_children(id, old_base, extra_label)
+    }
+
+    /// [`DynamicTrie::relocate`] but takes the base of the children, which
+    /// the unit of the node at `id` may not be able to encode.
+    fn move_children(
+        &mut self,
+        id: usize,
+        old_base: usize,
+        extra_label: Option<u8>,
+    ) -> Result<usize> {
+        let has_leaf = unit::has_leaf(self.units[id]);
+        let mut labels = Vec::new();
+        if has_leaf {
+            labels.push(0);
+        }
+        let children = children_at(&self.units, old_base)
+            .map(|(label, child)| (label, base(&self.units, child)))
+            .collect::<Vec<_>>();
+        labels.extend(children.iter().map(|&(label, _)| label));
+        let moved = labels.clone();
+        labels.extend(extra_label);
+
+        let new_base = self.find_base(id, &labels)?;
+        for &label in &moved {
+            let from = old_base ^ label as usize;
+            let to = new_base ^ label as usize;
+            self.use_unit(to);
+            self.units[to] = self.units[from];
+        }
+        for &(_, child_base) in &children {
+            *self.refs.get_mut(&child_base).unwrap() += 1;
+        }
+        self.set_node(id, has_leaf, new_base)?;
+        self.refs.insert(new_base, 1);
+
+        let old_refs = self.refs.get_mut(&old_base).unwrap();
+        *old_refs -= 1;
+        if *old_refs == 0 {
+            self.refs.remove(&old_base);
+            for &(_, child_base) in &children {
+                *self.refs.get_mut(&child_base).unwrap() -= 1;
+            }
+            for &label in &moved {
+                let from = old_base ^ label as usize;
+                self.units[from] = 0;
+                self.free.insert(from);
+            }
+        }
+
+        // Children moved far from their own children have those moved too.
+        for &(label, child_base) in &children {
+            let to = new_base ^ label as usize;
+            if can_reach(to, child_base) {
+                self.set_node(to, unit::has_leaf(self.units[to]), child_base)?;
+            } else {
+                self.move_children(to, child_base, None)?;
+            }
+        }
+        Ok(new_base)
+    }
+
+    /// Returns a base for the node at `id` which no other node has and whose
+    /// units for `labels` are free.
+    ///
+    /// Free units in the same window of 2^21 units as `id` can be reached
+    /// with any offset, so a bounded number of them is tried first. Farther
+    /// bases must have the low byte of `id`, which bases in the last blocks
+    /// are given, and the array grows by a block if none of them fits.
+    fn find_base(&mut self, id: usize, labels: &[u8]) -> Result<usize> {
+        // Nodes with the same base would share their children.
+        let fits = |dynamic: &DynamicTrie, base: usize| {
+            !dynamic.refs.contains_key(&base)
+                && labels
+                    .iter()
+                    .all(|&label| dynamic.free.contains(&(base ^ label as usize)))
+        };
+
+        let first_label = labels[0] as usize;
+        let window = id & !(NEAR_OFFSET - 1);
+        let near = self
+            .free
+            .range(id..window + NEAR_OFFSET)
+            .chain(self.free.range(window..id))
+            .take(MAX_CANDIDATES)
+            .map(|&slot| slot ^ first_label);
+        for base in near {
+            if fits(self, base) {
+                return Ok(base);
+            }
+        }
+
+        let num_blocks = self.units.len() / BLOCK_SIZE;
+        for block in num_blocks.saturating_sub(NUM_ALIGNED_BLOCKS)..num_blocks {
+            let base = (block * BLOCK_SIZE) | (id & (BLOCK_SIZE - 1));
+            if can_reach(id, base) && fits(self, base) {
+                return Ok(base);
+            }
+        }
+
+        let begin = self.units.len();
+        let base = begin | (id & (BLOCK_SIZE - 1));
+        if !can_reach(id, base) {
+            return Err(Error::TooManyUnits);
+        }
+        self.units.resize(begin + BLOCK_SIZE, 0);
+        self.free.extend(begin..begin + BLOCK_SIZE);
+        Ok(base)
+    }
+
+    /// Tests whether the node at `id` has children or a leaf.
+    fn has_base(&self, id: usize) -> bool {
+        unit::has_leaf(self.units[id]) || unit::children(&self.units, id).next().is_some()
+    }
+
+    /// Rewrites the unit of the node at `id` with a new base.
+    fn set_node(&mut self, id: usize, has_leaf: bool, base: usize) -> Result<()> {
+        let label = unit::label(self.units[id]) as u8;
+        self.units[id] = node_unit(label, has_leaf, id ^ base)?;
+        Ok(())
+    }
+
+    fn use_unit(&mut self, id: usize) {
+        self.free.remove(&id);
+    }
+}
+
+impl Default for DynamicTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the labels and positions of the children whose base is `base`.
+fn children_at(units: &[u32], base: usize) -> impl Iterator<Item = (u8, usize)> + '_ {
+    (1..=0xFF).filter_map(move |label: u8| {
+        let child = base ^ label as usize;
+        let unit = *units.get(child)?;
+        (unit::label(unit) == label as u32).then_some((label, child))
+    })
+}
+
+/// Returns the position of the children and the leaf of the node at `id`.
+fn base(units: &[u32], id: usize) -> usize {
+    id ^ unit::offset(units[id]) as usize
+}
+
+/// Tests whether a node at `id` can have its children at `base`, i.e. the
+/// offset between them can be encoded.
+fn can_reach(id: usize, base: usize) -> bool {
+    node_unit(0, false, id ^ base).is_ok()
+}
+
+/// [`layout::node_unit`] but fails if the offset is not encoded as it is.
+fn node_unit(label: u8, has_leaf: bool, offset: usize) -> Result<u32> {
+    layout::node_unit(label, has_leaf, offset)
+        .filter(|&unit| unit::offset(unit) as usize == offset)
+        .ok_or(Error::TooManyUnits)
+}
@@ -0,0 +1,109 @@
+//! Module for errors.
+
+use std::{error, fmt, io};
+
+/// Type of errors which can occur when building or loading a dictionary.
+#[derive(Debug)]
+pub enum Error {
+    /// Darts-clone failed, with the message it reported.
+    Darts(String),
+    /// The key was given more than once although duplicates are not allowed.
+    DuplicateKey(Vec<u8>),
+    /// The key at `index` is not greater than the previous key in byte order.
+    WrongKeyOrder { index: usize, key: Vec<u8> },
+    /// The key at `index` is empty or contains a null character.
+    InvalidKey { index: usize, key: Vec<u8> },
+    /// The value at `index` is negative.
+    NegativeValue { index: usize, value: i32 },
+    /// The value at `index` is greater than [`MAX_VALUE`], as Darts-clone
+    /// stores 31 bits of each value.
+    ///
+    /// [`MAX_VALUE`]: crate::darts::MAX_VALUE
+    ValueOutOfRange { index: usize, value: u32 },
+    /// Fewer `what`, e.g. values, were given than the `num_keys` keys to
+    /// build.
+    CountMismatch {
+        what: &'static str,
+        count: usize,
+        num_keys: usize,
+    },
+    /// The record starting at `line` of a text input is malformed, counting
+    /// lines from 1.
+    Parse { line: usize, message: String },
+    /// The file is not in the expected format.
+    InvalidFormat(String),
+    /// No section of an archive has the given name.
+    SectionNotFound(String),
+    /// The checksum of the data does not match the one stored with it.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The dictionary needs more units than a double array can address.
+    TooManyUnits,
+    /// Reading or writing a file failed.
+    Io(io::Error),
+}
+
+/// Type of results whose error is [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Darts(what) => write!(f, "{}", what),
+            Error::DuplicateKey(key) => {
+                write!(f, "duplicate key: {:?}", String::from_utf8_lossy(key))
+            }
+            Error::WrongKeyOrder { index, key } => write!(
+                f,
+                "wrong key order at index {}: {:?}",
+                index,
+                String::from_utf8_lossy(key)
+            ),
+            Error::InvalidKey { index, key } => write!(
+                f,
+                "invalid key at index {}: {:?}",
+                index,
+                String::from_utf8_lossy(key)
+            ),
+            Error::NegativeValue { index, value } => {
+                write!(f, "negative value at index {}: {}", index, value)
+            }
+            Error::ValueOutOfRange { index, value } => write!(
+                f,
+                "value out of range at index {}: {} is greater than {}",
+                index,
+                value,
+                crate::darts::MAX_VALUE
+            ),
+            Error::CountMismatch {
+                what,
+                count,
+                num_keys,
+            } => write!(f, "{} {} given for {} keys", count, what, num_keys),
+            Error::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Error::InvalidFormat(what) => write!(f, "invalid format: {}", what),
+            Error::SectionNotFound(name) => write!(f, "section not found: {:?}", name),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, found {:08x}",
+                expected, actual
+            ),
+            Error::TooManyUnits => write!(f, "too many units for a double array"),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,539 @@
+//! Module for building dictionaries which do not fit in memory.
+//!
+//! The construction streams the keys twice through temporary files. First,
+//! the nodes of the trie are written in post-order while the sorted keys are
+//! read, so that only the path to the current key is kept in memory. Then the
+//! nodes are read back in reverse, which visits each parent before its
+//! children, and placed into units which are written to the dictionary file
+//! as soon as their block is fixed.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    env,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    vec,
+};
+
+use crate::{
+    builder::{check_pair, BuildReport},
+    error::{Error, Result},
+    header::ByteOrder,
+    layout::{Arranger, UnitSink, BLOCK_SIZE},
+};
+
+/// Default memory budget of [`ExternalBuilder`], in bytes.
+pub const DEFAULT_MEMORY_LIMIT: usize = 256 << 20;
+
+/// Default maximum number of runs merged at once by [`ExternalBuilder`].
+pub const DEFAULT_MAX_FAN_IN: usize = 64;
+
+const CHUNK_SIZE: usize = 64 << 10;
+
+/// Builder which writes a dictionary file using a bounded amount of memory.
+///
+/// Key-value pairs given in any order with [`ExternalBuilder::push`] are
+/// sorted with an external merge sort, and pairs already in key order can be
+/// streamed with [`ExternalBuilder::build_sorted`]. As with
+/// [`DoubleArrayTrie::build`](crate::darts::DoubleArrayTrie::build), only the
+/// first pair of duplicate keys is kept. The resultant file can be loaded with
+/// [`DoubleArrayTrie::open`](crate::darts::DoubleArrayTrie::open).
+///
+/// Note that the dictionary is a trie rather than a DAWG, so it can be larger
+/// than one built in memory with values.
+///
+/// ```ignore
+/// use darts::external::ExternalBuilder;
+///
+/// let mut builder = ExternalBuilder::new().memory_limit(64 << 20);
+/// for (key, value) in pairs {
+///     builder.push(key, value)?;
+/// }
+/// let report = builder.finish("path/to/dict")?;
+/// ```
+pub struct ExternalBuilder {
+    temp_dir: PathBuf,
+    memory_limit: usize,
+    max_fan_in: usize,
+    pairs: Vec<(Vec<u8>, i32)>,
+    num_bytes: usize,
+    num_pairs: usize,
+    runs: Vec<TempFile>,
+}
+
+impl ExternalBuilder {
+    /// Constructs a builder which uses the temporary directory of the system
+    /// and at most [`DEFAULT_MEMORY_LIMIT`] bytes for buffering.
+    pub fn new() -> ExternalBuilder {
+        ExternalBuilder {
+            temp_dir: env::temp_dir(),
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            max_fan_in: DEFAULT_MAX_FAN_IN,
+            pairs: Vec::new(),
+            num_bytes: 0,
+            num_pairs: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Sets the directory for temporary files. They are removed when the
+    /// builder is dropped.
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> ExternalBuilder {
+        self.temp_dir = dir.into();
+        self
+    }
+
+    /// Sets the approximate number of bytes used to buffer key-value pairs
+    /// and units before they are written to temporary files.
+    pub fn memory_limit(mut self, bytes: usize) -> ExternalBuilder {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Sets the maximum number of temporary files merged at once, so that
+    /// the builder does not run out of file descriptors. Runs beyond it are
+    /// merged in several passes. The number is at least 2.
+    pub fn max_fan_in(mut self, max_fan_in: usize) -> ExternalBuilder {
+        self.max_fan_in = max_fan_in.max(2);
+        self
+    }
+
+    /// Adds a key-value pair in any order. The key must not be empty nor
+    /// contain a null character, and the value must not be negative. Once
+    /// the buffered pairs exceed the memory limit, they are sorted and written
+    /// to a temporary file.
+    pub fn push<K: AsRef<[u8]>>(&mut self, key: K, value: i32) -> Result<()> {
+        let key = key.as_ref();
+        check_pair(self.num_pairs, key, value)?;
+        self.num_pairs += 1;
+        self.num_bytes += key.len() + mem::size_of::<(Vec<u8>, i32)>();
+        self.pairs.push((key.to_vec(), value));
+        if self.num_bytes >= self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        let mut pairs = mem::take(&mut self.pairs);
+        self.num_bytes = 0;
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let run = TempFile::new(&self.temp_dir)?;
+        let mut out = BufWriter::with_capacity(CHUNK_SIZE, File::create(&run.path)?);
+        for (key, value) in pairs {
+            write_pair(&mut out, &key, value)?;
+        }
+        out.flush()?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Merges the runs in groups of at most `max_fan_in`, keeping them in
+    /// the order they were written, until they can be merged at once.
+    fn merge_runs(&mut self) -> Result<()> {
+        while self.runs.len() > self.max_fan_in {
+            let mut runs = mem::take(&mut self.runs).into_iter().peekable();
+            while runs.peek().is_some() {
+                let group = runs.by_ref().take(self.max_fan_in).collect::<Vec<_>>();
+                let run = self.merge_group(group)?;
+                self.runs.push(run);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_group(&self, mut group: Vec<TempFile>) -> Result<TempFile> {
+        if group.len() == 1 {
+            return Ok(group.pop().unwrap());
+        }
+        let mut merger = Merger::new(open_runs(&group)?)?;
+        let run = TempFile::new(&self.temp_dir)?;
+        let mut out = BufWriter::with_capacity(CHUNK_SIZE, File::create(&run.path)?);
+        while let Some((key, value)) = merger.next()? {
+            write_pair(&mut out, &key, value)?;
+        }
+        out.flush()?;
+        Ok(run)
+    }
+
+    /// Merges the pairs added by [`ExternalBuilder::push`] and writes the
+    /// dictionary to `file_name`.
+    pub fn finish<P: AsRef<Path>>(mut self, file_name: P) -> Result<BuildReport> {
+        self.merge_runs()?;
+        let mut sources = open_runs(&self.runs)?;
+        let mut pairs = mem::take(&mut self.pairs);
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        sources.push(Source::Memory(pairs.into_iter()));
+
+        let mut merger = Merger::new(sources)?;
+        let mut nodes = NodeWriter::new(&self.temp_dir)?;
+        while let Some((key, value)) = merger.next()? {
+            nodes.insert(&key, value)?;
+        }
+        self.write_units(nodes, file_name.as_ref())
+    }
+
+    /// Writes the dictionary of key-value pairs given in key order to
+    /// `file_name`, without sorting them. Unlike [`ExternalBuilder::finish`],
+    /// pairs added by [`ExternalBuilder::push`] are ignored.
+    pub fn build_sorted<I, K, P>(&self, pairs: I, file_name: P) -> Result<BuildReport>
+    where
+        I: IntoIterator<Item = (K, i32)>,
+        K: AsRef<[u8]>,
+        P: AsRef<Path>,
+    {
+        let mut nodes = NodeWriter::new(&self.temp_dir)?;
+        for (index, (key, value)) in pairs.into_iter().enumerate() {
+            let key = key.as_ref();
+            check_pair(index, key, value)?;
+            if nodes.insert(key, value)? == Insertion::WrongOrder {
+                return Err(Error::WrongKeyOrder {
+                    index,
+                    key: key.to_vec(),
+                });
+            }
+        }
+        self.write_units(nodes, file_name.as_ref())
+    }
+
+    fn write_units(&self, nodes: NodeWriter, file_name: &Path) -> Result<BuildReport> {
+        let (records, report) = nodes.finish()?;
+        let mut records = ReverseReader::new(File::open(&records.path)?)?;
+
+        let max_patches = (self.memory_limit / mem::size_of::<(usize, u32)>()).max(1);
+        let mut arranger = Arranger::new(FileSink::create(file_name, max_patches)?)?;
+        let mut pending = vec![(0, 0)];
+        let mut labels = Vec::with_capacity(256);
+        while !records.is_empty() {
+            let (id, label) = pending.pop().expect("node records out of sync");
+            let value = records.read_record(&mut labels)?;
+            let offset = arranger.arrange(id, label, &labels, value)?;
+            for &child_label in labels.iter().filter(|&&label| label != 0) {
+                pending.push((offset ^ child_label as usize, child_label));
+            }
+        }
+        let (sink, _) = arranger.finish()?;
+        sink.finish()?;
+        Ok(report)
+    }
+}
+
+impl Default for ExternalBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Temporary file removed when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new(dir: &Path) -> io::Result<TempFile> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("darts-{}-{}.tmp", process::id(), id));
+        File::create(&path)?;
+        Ok(TempFile { path })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn write_pair<W: Write>(out: &mut W, key: &[u8], value: i32) -> io::Result<()> {
+    out.write_all(&(key.len() as u32).to_le_bytes())?;
+    out.write_all(key)?;
+    out.write_all(&value.to_le_bytes())
+}
+
+fn open_runs(runs: &[TempFile]) -> io::Result<Vec<Source>> {
+    let mut sources = Vec::with_capacity(runs.len() + 1);
+    for run in runs {
+        sources.push(Source::Run(BufReader::with_capacity(
+            CHUNK_SIZE,
+            File::open(&run.path)?,
+        )));
+    }
+    Ok(sources)
+}
+
+/// Sorted sequence of key-value pairs to be merged.
+enum Source {
+    Run(BufReader<File>),
+    Memory(vec::IntoIter<(Vec<u8>, i32)>),
+}
+
+impl Source {
+    fn next(&mut self) -> io::Result<Option<(Vec<u8>, i32)>> {
+        let reader = match self {
+            Source::Run(reader) => reader,
+            Source::Memory(pairs) => return Ok(pairs.next()),
+        };
+        let mut word = [0; 4];
+        match reader.read_exact(&mut word) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let mut key = vec![0; u32::from_le_bytes(word) as usize];
+        reader.read_exact(&mut key)?;
+        reader.read_exact(&mut word)?;
+        Ok(Some((key, i32::from_le_bytes(word))))
+    }
+}
+
+/// Merges sources in key order. Pairs with the same key are returned in the
+/// order of their sources, which are in the order the pairs were added.
+struct Merger {
+    sources: Vec<Source>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize, i32)>>,
+}
+
+impl Merger {
+    fn new(mut sources: Vec<Source>) -> io::Result<Merger> {
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (index, source) in sources.iter_mut().enumerate() {
+            if let Some((key, value)) = source.next()? {
+                heap.push(Reverse((key, index, value)));
+            }
+        }
+        Ok(Merger { sources, heap })
+    }
+
+    fn next(&mut self) -> io::Result<Option<(Vec<u8>, i32)>> {
+        let Reverse((key, index, value)) = match self.heap.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if let Some((next_key, next_value)) = self.sources[index].next()? {
+            self.heap.push(Reverse((next_key, index, next_value)));
+        }
+        Ok(Some((key, value)))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Insertion {
+    Inserted,
+    Duplicate,
+    WrongOrder,
+}
+
+#[derive(Default)]
+struct OpenNode {
+    labels: Vec<u8>,
+    value: i32,
+}
+
+/// Writes the nodes of the trie of sorted keys in post-order. Each record
+/// holds the labels of the children, 0 standing for the leaf, followed by the
+/// value of the leaf and the number of labels.
+struct NodeWriter {
+    file: TempFile,
+    out: BufWriter<File>,
+    path: Vec<OpenNode>,
+    last_key: Vec<u8>,
+    report: BuildReport,
+}
+
+impl NodeWriter {
+    fn new(temp_dir: &Path) -> io::Result<NodeWriter> {
+        let file = TempFile::new(temp_dir)?;
+        let out = BufWriter::with_capacity(CHUNK_SIZE, File::create(&file.path)?);
+        Ok(NodeWriter {
+            file,
+            out,
+            path: Vec::new(),
+            last_key: Vec::new(),
+            report: BuildReport {
+                num_keys: 0,
+                num_duplicates: 0,
+            },
+        })
+    }
+
+    fn insert(&mut self, key: &[u8], value: i32) -> io::Result<Insertion> {
+        if self.path.is_empty() {
+            self.path.push(OpenNode::default());
+        } else if key == self.last_key.as_slice() {
+            self.report.num_duplicates += 1;
+            return Ok(Insertion::Duplicate);
+        } else if key < self.last_key.as_slice() {
+            return Ok(Insertion::WrongOrder);
+        }
+
+        let prefix_len = key
+            .iter()
+            .zip(&self.last_key)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while self.path.len() > prefix_len + 1 {
+            let node = self.path.pop().unwrap();
+            self.write(&node)?;
+        }
+        for &label in &key[prefix_len..] {
+            self.path.last_mut().unwrap().labels.push(label);
+            self.path.push(OpenNode::default());
+        }
+        let leaf = self.path.last_mut().unwrap();
+        leaf.labels.push(0);
+        leaf.value = value;
+
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.report.num_keys += 1;
+        Ok(Insertion::Inserted)
+    }
+
+    fn write(&mut self, node: &OpenNode) -> io::Result<()> {
+        self.out.write_all(&node.labels)?;
+        self.out.write_all(&node.value.to_le_bytes())?;
+        self.out
+            .write_all(&(node.labels.len() as u16).to_le_bytes())
+    }
+
+    fn finish(mut self) -> io::Result<(TempFile, BuildReport)> {
+        while let Some(node) = self.path.pop() {
+            self.write(&node)?;
+        }
+        self.out.flush()?;
+        Ok((self.file, self.report))
+    }
+}
+
+/// Reads records of a file from its end.
+struct ReverseReader {
+    file: File,
+    // Bytes between `pos` and `pos + buf.len()` which are not read yet.
+    pos: u64,
+    buf: Vec<u8>,
+}
+
+impl ReverseReader {
+    fn new(mut file: File) -> io::Result<ReverseReader> {
+        let pos = file.seek(SeekFrom::End(0))?;
+        Ok(ReverseReader {
+            file,
+            pos,
+            buf: Vec::new(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == 0 && self.buf.is_empty()
+    }
+
+    /// Moves the last `len` bytes into `out`.
+    fn read_back(&mut self, len: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        while self.buf.len() < len {
+            if self.pos == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let chunk_len = self.pos.min(CHUNK_SIZE as u64);
+            self.pos -= chunk_len;
+            let mut chunk = vec![0; chunk_len as usize];
+            self.file.seek(SeekFrom::Start(self.pos))?;
+            self.file.read_exact(&mut chunk)?;
+            chunk.extend_from_slice(&self.buf);
+            self.buf = chunk;
+        }
+        let begin = self.buf.len() - len;
+        out.clear();
+        out.extend_from_slice(&self.buf[begin..]);
+        self.buf.truncate(begin);
+        Ok(())
+    }
+
+    /// Reads the previous node record into `labels` and returns its value.
+    fn read_record(&mut self, labels: &mut Vec<u8>) -> io::Result<i32> {
+        self.read_back(2, labels)?;
+        let num_labels = u16::from_le_bytes([labels[0], labels[1]]) as usize;
+        self.read_back(4, labels)?;
+        let value = i32::from_le_bytes([labels[0], labels[1], labels[2], labels[3]]);
+        self.read_back(num_labels, labels)?;
+        Ok(value)
+    }
+}
+
+/// Writes units to a file in little-endian, as
+/// [`DoubleArrayTrie::save`](crate::darts::DoubleArrayTrie::save) does,
+/// keeping only the blocks which are not fixed yet in memory. Units of fixed
+/// blocks are patched in batches.
+struct FileSink {
+    out: BufWriter<File>,
+    window: VecDeque<u32>,
+    window_begin: usize,
+    patches: Vec<(usize, u32)>,
+    max_patches: usize,
+}
+
+impl FileSink {
+    fn create(file_name: &Path, max_patches: usize) -> io::Result<FileSink> {
+        Ok(FileSink {
+            out: BufWriter::with_capacity(CHUNK_SIZE, File::create(file_name)?),
+            window: VecDeque::new(),
+            window_begin: 0,
+            patches: Vec::new(),
+            max_patches,
+        })
+    }
+
+    fn apply_patches(&mut self) -> io::Result<()> {
+        // The sort is stable, so the last patch of a unit wins.
+        self.patches.sort_by_key(|&(id, _)| id);
+        for &(id, unit) in &self.patches {
+            self.out.seek(SeekFrom::Start((id * 4) as u64))?;
+            self.out.write_all(&ByteOrder::Little.encode(unit))?;
+        }
+        self.out.seek(SeekFrom::End(0))?;
+        self.patches.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        for unit in mem::take(&mut self.window) {
+            self.out.write_all(&ByteOrder::Little.encode(unit))?;
+        }
+        self.apply_patches()?;
+        self.out.flush()
+    }
+}
+
+impl UnitSink for FileSink {
+    fn grow(&mut self) -> io::Result<()> {
+        self.window.resize(self.window.len() + BLOCK_SIZE, 0);
+        Ok(())
+    }
+
+    fn set(&mut self, id: usize, unit: u32) -> io::Result<()> {
+        if id >= self.window_begin {
+            self.window[id - self.window_begin] = unit;
+            return Ok(());
+        }
+        self.patches.push((id, unit));
+        if self.patches.len() >= self.max_patches {
+            self.apply_patches()?;
+        }
+        Ok(())
+    }
+
+    fn retire(&mut self, block: usize) -> io::Result<()> {
+        debug_assert_eq!(block * BLOCK_SIZE, self.window_begin);
+        for unit in self.window.drain(..BLOCK_SIZE) {
+            self.out.write_all(&ByteOrder::Little.encode(unit))?;
+        }
+        self.window_begin += BLOCK_SIZE;
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+//! Module for the self-describing file format.
+//!
+//! A file starts with a header of [`HEADER_SIZE`] bytes, whose fields are
+//! little-endian, followed by the array of units, which are little-endian as
+//! well unless the byte order mark says otherwise:
+//!
+//! | Offset | Size | Field                          |
+//! |--------|------|--------------------------------|
+//! | 0      | 8    | [`MAGIC`]                      |
+//! | 8      | 2    | version                        |
+//! | 10     | 2    | unit size                      |
+//! | 12     | 4    | flags                          |
+//! | 16     | 8    | number of units                |
+//! | 24     | 8    | number of keys                 |
+//! | 32     | 4    | CRC-32 of the array of units   |
+//! | 36     | 4    | 0x0A0B0C0D in the unit order   |
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    compress::COMPRESSED_MAGIC,
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+};
+
+/// Bytes which identify the file format.
+pub const MAGIC: [u8; 8] = *b"DARTSRS\0";
+/// Version of the file format written by this crate.
+pub const VERSION: u16 = 2;
+/// Number of bytes before the array of units.
+pub const HEADER_SIZE: usize = 40;
+
+/// Flag telling that the value of each key is its index in byte order, as
+/// when a dictionary is built without values.
+pub const FLAG_INDEX_VALUES: u32 = 1 << 0;
+
+const BYTE_ORDER_MARK: u32 = 0x0A0B_0C0D;
+
+/// Byte order of the units in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Byte order of the host.
+    pub const NATIVE: ByteOrder = if cfg!(target_endian = "big") {
+        ByteOrder::Big
+    } else {
+        ByteOrder::Little
+    };
+
+    /// Returns the bytes of `unit` in this order.
+    pub(crate) fn encode(self, unit: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => unit.to_le_bytes(),
+            ByteOrder::Big => unit.to_be_bytes(),
+        }
+    }
+
+    /// Returns the units stored in `bytes` in this order.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| {
+                let chunk = chunk.try_into().unwrap();
+                match self {
+                    ByteOrder::Little => u32::from_le_bytes(chunk),
+                    ByteOrder::Big => u32::from_be_bytes(chunk),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Description of the array of units stored in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Version of the file format.
+    pub version: u16,
+    /// Number of bytes of each unit.
+    pub unit_size: u16,
+    /// Flags describing the values, e.g. [`FLAG_INDEX_VALUES`].
+    pub flags: u32,
+    /// Number of units in the array.
+    pub num_units: u64,
+    /// Number of keys in the dictionary.
+    pub num_keys: u64,
+    /// CRC-32 of the bytes of the array.
+    pub checksum: u32,
+    /// Byte order of the units.
+    pub byte_order: ByteOrder,
+}
+
+impl Header {
+    /// Returns the number of bytes of the array of units. Headers decoded by
+    /// [`Header::from_bytes`] have a size which fits in a `usize`.
+    pub fn payload_size(&self) -> u64 {
+        self.num_units.saturating_mul(self.unit_size as u64)
+    }
+
+    /// Encodes the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.unit_size.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.num_units.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.num_keys.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes[36..40].copy_from_slice(&self.byte_order.encode(BYTE_ORDER_MARK));
+        bytes
+    }
+
+    /// Decodes a header, checking the magic bytes, the version, the unit
+    /// size, the byte order and the number of units.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Header> {
+        if bytes.len() < HEADER_SIZE || bytes[0..8] != MAGIC {
+            return Err(Error::InvalidFormat("missing magic bytes".to_owned()));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let header = Header {
+            version: u16_at(8),
+            unit_size: u16_at(10),
+            flags: u32_at(12),
+            num_units: u64_at(16),
+            num_keys: u64_at(24),
+            checksum: u32_at(32),
+            byte_order: match bytes[36..40] {
+                ref mark if *mark == BYTE_ORDER_MARK.to_le_bytes() => ByteOrder::Little,
+                ref mark if *mark == BYTE_ORDER_MARK.to_be_bytes() => ByteOrder::Big,
+                _ => return Err(Error::InvalidFormat("unknown byte order".to_owned())),
+            },
+        };
+        if header.version != VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "unsupported version {}",
+                header.version
+            )));
+        }
+        if header.unit_size != 4 {
+            return Err(Error::InvalidFormat(format!(
+                "unsupported unit size {}",
+                header.unit_size
+            )));
+        }
+        // An empty array is never written, and a size of 0 would make
+        // `DoubleArrayTrie::open` read to the end of the file.
+        match header.num_units.checked_mul(header.unit_size as u64) {
+            Some(0) => return Err(Error::InvalidFormat("empty array".to_owned())),
+            Some(size) if usize::try_from(size).is_ok() => {}
+            _ => {
+                return Err(Error::InvalidFormat(format!(
+                    "too many units: {}",
+                    header.num_units
+                )))
+            }
+        }
+        Ok(header)
+    }
+}
+
+impl DoubleArrayTrie {
+    /// Returns the header describing the array of units, with the given
+    /// flags. The keys are counted by walking the array.
+    pub fn header(&self, flags: u32) -> Header {
+        self.header_in(flags, ByteOrder::Little)
+    }
+
+    fn header_in(&self, flags: u32, byte_order: ByteOrder) -> Header {
+        let units = self.array();
+        let mut hasher = crc32fast::Hasher::new();
+        for &unit in units {
+            hasher.update(&byte_order.encode(unit));
+        }
+        Header {
+            version: VERSION,
+            unit_size: self.unit_size() as u16,
+            flags,
+            num_units: units.len() as u64,
+            num_keys: self.iter().count() as u64,
+            checksum: hasher.finalize(),
+            byte_order,
+        }
+    }
+
+    /// Writes a header and the array of units into the specified file.
+    /// `flags` is stored as is, e.g. [`FLAG_INDEX_VALUES`].
+    pub fn save_with_header(&self, file_name: &str, flags: u32) -> Result<Header> {
+        let mut out = BufWriter::new(File::create(file_name)?);
+        let header = self.write_with_header(&mut out, flags)?;
+        out.flush()?;
+        Ok(header)
+    }
+
+    /// [`DoubleArrayTrie::save_with_header`] but writes to `out`.
+    pub fn write_with_header<W: Write>(&self, out: &mut W, flags: u32) -> Result<Header> {
+        self.write_with_header_in(out, flags, ByteOrder::Little)
+    }
+
+    /// [`DoubleArrayTrie::write_with_header`] but writes the units in the
+    /// given byte order.
+    pub(crate) fn write_with_header_in<W: Write>(
+        &self,
+        out: &mut W,
+        flags: u32,
+        byte_order: ByteOrder,
+    ) -> Result<Header> {
+        let units = self.array();
+        if units.is_empty() {
+            return Err(Error::InvalidFormat("no array to save".to_owned()));
+        }
+        let header = self.header_in(flags, byte_order);
+        out.write_all(&header.to_bytes())?;
+        for &unit in units {
+            out.write_all(&byte_order.encode(unit))?;
+        }
+        Ok(header)
+    }
+
+    /// Reads a file written by [`DoubleArrayTrie::save_with_header`],
+    /// skipping `offset` bytes first. The magic bytes, the version, the unit
+    /// size, the length and the checksum are verified before the array is
+    /// replaced, and the units are checked as by
+    /// [`DoubleArrayTrie::from_units`]. Units in the other byte order than the
+    /// host are swapped.
+    pub fn open_with_header(&mut self, file_name: &str, offset: usize) -> Result<Header> {
+        let mut file = BufReader::new(File::open(file_name)?);
+        file.seek(SeekFrom::Start(offset as u64))?;
+        self.read_section(&mut file, true)
+    }
+
+    /// [`DoubleArrayTrie::open_with_header`] but reads from `reader`, which
+    /// must be at the start of the header. The units are copied into an
+    /// array owned by the dictionary.
+    pub fn read_with_header<R: Read>(&mut self, reader: &mut R) -> Result<Header> {
+        self.read_section(reader, true)
+    }
+
+    /// Reads the header and the array of units once, and replaces the array
+    /// if they are valid. The checksum is only checked if `verify` is true.
+    pub(crate) fn read_section<R: Read>(&mut self, reader: &mut R, verify: bool) -> Result<Header> {
+        let mut bytes = [0; HEADER_SIZE];
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|_| Error::InvalidFormat("missing magic bytes".to_owned()))?;
+        let header = Header::from_bytes(&bytes)?;
+
+        let mut payload = Vec::new();
+        reader
+            .take(header.payload_size())
+            .read_to_end(&mut payload)?;
+        if payload.len() as u64 != header.payload_size() {
+            return Err(Error::InvalidFormat(format!(
+                "truncated array: {} of {} bytes",
+                payload.len(),
+                header.payload_size()
+            )));
+        }
+        if verify {
+            let checksum = crc32fast::hash(&payload);
+            if checksum != header.checksum {
+                return Err(Error::ChecksumMismatch {
+                    expected: header.checksum,
+                    actual: checksum,
+                });
+            }
+        }
+        *self = DoubleArrayTrie::from_units(header.byte_order.decode(&payload))?;
+        Ok(header)
+    }
+
+    /// Reads any format: a file starting with [`MAGIC`] is read with
+    /// [`DoubleArrayTrie::open_with_header`] and a file starting with
+    /// [`COMPRESSED_MAGIC`] is decompressed with a built-in codec, both
+    /// returning their header, while any other file is read as a raw array
+    /// of units with [`DoubleArrayTrie::open`].
+    pub fn open_any(&mut self, file_name: &str, offset: usize) -> Result<Option<Header>> {
+        let mut file = File::open(file_name)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut magic = [0; MAGIC.len()];
+        let is_read = file.read_exact(&mut magic).is_ok();
+        if is_read && magic == MAGIC {
+            return self.open_with_header(file_name, offset).map(Some);
+        }
+        if is_read && magic == COMPRESSED_MAGIC {
+            file.seek(SeekFrom::Start(offset as u64))?;
+            return self.read_compressed(&mut BufReader::new(file)).map(Some);
+        }
+        self.open(file_name, "rb", offset, 0)
+            .map_err(|what| Error::Darts(what.to_owned()))?;
+        Ok(None)
+    }
+}
@@ -0,0 +1,268 @@
+//! Module for placing nodes into a double array.
+//!
+//! It follows the construction algorithm of Darts-clone: units are allocated
+//! in blocks of 256 and only the last blocks are searched for free units, the
+//! older ones being fixed. This lets units be produced incrementally, because
+//! a fixed block is never modified again except for the units of nodes whose
+//! children have not been placed yet.
+
+use std::io;
+
+use crate::error::{Error, Result};
+
+pub(crate) const BLOCK_SIZE: usize = 256;
+const NUM_EXTRA_BLOCKS: usize = 16;
+const NUM_EXTRAS: usize = BLOCK_SIZE * NUM_EXTRA_BLOCKS;
+
+const LOWER_MASK: usize = 0xFF;
+const UPPER_MASK: usize = 0xFF << 21;
+const MAX_OFFSET: usize = 1 << 29;
+
+/// Destination of the units being placed.
+pub(crate) trait UnitSink {
+    /// Appends a block of units initialized with 0.
+    fn grow(&mut self) -> io::Result<()>;
+
+    /// Overwrites the unit at `id`.
+    fn set(&mut self, id: usize, unit: u32) -> io::Result<()>;
+
+    /// Tells that the block is fixed, i.e. only the units of nodes which are
+    /// not arranged yet will be set again.
+    fn retire(&mut self, block: usize) -> io::Result<()>;
+}
+
+impl UnitSink for Vec<u32> {
+    fn grow(&mut self) -> io::Result<()> {
+        self.resize(self.len() + BLOCK_SIZE, 0);
+        Ok(())
+    }
+
+    fn set(&mut self, id: usize, unit: u32) -> io::Result<()> {
+        self[id] = unit;
+        Ok(())
+    }
+
+    fn retire(&mut self, _block: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns a unit holding `value`.
+pub(crate) fn value_unit(value: i32) -> u32 {
+    value as u32 | (1 << 31)
+}
+
+/// Returns a unit of a node with the given label, leaf flag and offset to its
+/// children, or [`None`] if the offset can not be encoded.
+pub(crate) fn node_unit(label: u8, has_leaf: bool, offset: usize) -> Option<u32> {
+    let mut unit = label as u32 | (has_leaf as u32) << 8;
+    if offset >= MAX_OFFSET {
+        return None;
+    } else if offset < 1 << 21 {
+        unit |= (offset as u32) << 10;
+    } else {
+        unit |= (offset as u32) << 2 | 1 << 9;
+    }
+    Some(unit)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Extra {
+    prev: usize,
+    next: usize,
+    is_fixed: bool,
+    is_used: bool,
+}
+
+/// Places nodes one by one. The parent of a node must be arranged before the
+/// node itself, and each node is identified by the position its parent
+/// reserved for it.
+pub(crate) struct Arranger<S> {
+    sink: S,
+    num_units: usize,
+    extras: Vec<Extra>,
+    extras_head: usize,
+}
+
+impl<S: UnitSink> Arranger<S> {
+    /// Constructs an arranger whose root is at position 0.
+    pub(crate) fn new(sink: S) -> Result<Arranger<S>> {
+        let mut arranger = Arranger {
+            sink,
+            num_units: 0,
+            extras: vec![Extra::default(); NUM_EXTRAS],
+            extras_head: 0,
+        };
+        arranger.reserve(0)?;
+        arranger.extra(0).is_used = true;
+        // An empty dictionary keeps this offset, which must not be 0.
+        arranger.sink.set(0, node_unit(0, false, 1).unwrap())?;
+        Ok(arranger)
+    }
+
+    /// Constructs an arranger whose first `num_blocks` blocks are fixed and
+    /// left to the caller. Nodes arranged in them get offsets beyond these
+    /// blocks, whose low byte is the same as the low byte of their position.
+    pub(crate) fn with_fixed_blocks(sink: S, num_blocks: usize) -> Result<Arranger<S>> {
+        let mut arranger = Arranger {
+            sink,
+            num_units: 0,
+            extras: vec![Extra::default(); NUM_EXTRAS],
+            extras_head: 0,
+        };
+        for id in 0..num_blocks * BLOCK_SIZE {
+            arranger.reserve(id)?;
+            arranger.extra(id).is_used = true;
+        }
+        Ok(arranger)
+    }
+
+    fn extra(&mut self, id: usize) -> &mut Extra {
+        &mut self.extras[id % NUM_EXTRAS]
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.num_units / BLOCK_SIZE
+    }
+
+    /// Places the children of the node at `id`, whose own label is `label`.
+    /// `labels` must be sorted and start with 0 if a key ends at the node, in
+    /// which case `value` is its value. Returns the offset of the children,
+    /// i.e. the child labeled `c` is at `offset ^ c`.
+    pub(crate) fn arrange(
+        &mut self,
+        id: usize,
+        label: u8,
+        labels: &[u8],
+        value: i32,
+    ) -> Result<usize> {
+        let offset = self.find_valid_offset(id, labels);
+        let has_leaf = labels.first() == Some(&0);
+        let unit = node_unit(label, has_leaf, id ^ offset).ok_or(Error::TooManyUnits)?;
+        self.sink.set(id, unit)?;
+
+        for &child_label in labels {
+            let child = offset ^ child_label as usize;
+            self.reserve(child)?;
+            if child_label == 0 {
+                self.sink.set(child, value_unit(value))?;
+            } else {
+                self.sink.set(child, child_label as u32)?;
+            }
+        }
+        self.extra(offset).is_used = true;
+        Ok(offset)
+    }
+
+    /// Fixes the remaining blocks and returns the sink. The number of units is
+    /// a multiple of the block size.
+    pub(crate) fn finish(mut self) -> Result<(S, usize)> {
+        let begin = self.num_blocks().saturating_sub(NUM_EXTRA_BLOCKS);
+        for block in begin..self.num_blocks() {
+            self.fix_block(block)?;
+        }
+        Ok((self.sink, self.num_units))
+    }
+
+    fn find_valid_offset(&mut self, id: usize, labels: &[u8]) -> usize {
+        if self.extras_head < self.num_units {
+            let mut unfixed_id = self.extras_head;
+            loop {
+                let offset = unfixed_id ^ labels[0] as usize;
+                if self.is_valid_offset(id, offset, labels) {
+                    return offset;
+                }
+                unfixed_id = self.extra(unfixed_id).next;
+                if unfixed_id == self.extras_head {
+                    break;
+                }
+            }
+        }
+        self.num_units | (id & LOWER_MASK)
+    }
+
+    fn is_valid_offset(&mut self, id: usize, offset: usize, labels: &[u8]) -> bool {
+        if self.extra(offset).is_used {
+            return false;
+        }
+        let rel_offset = id ^ offset;
+        if rel_offset & LOWER_MASK != 0 && rel_offset & UPPER_MASK != 0 {
+            return false;
+        }
+        labels[1..]
+            .iter()
+            .all(|&label| !self.extra(offset ^ label as usize).is_fixed)
+    }
+
+    fn reserve(&mut self, id: usize) -> Result<()> {
+        if id >= self.num_units {
+            self.expand()?;
+        }
+        if id == self.extras_head {
+            self.extras_head = self.extra(id).next;
+            if self.extras_head == id {
+                self.extras_head = self.num_units;
+            }
+        }
+        let Extra { prev, next, .. } = *self.extra(id);
+        self.extra(prev).next = next;
+        self.extra(next).prev = prev;
+        self.extra(id).is_fixed = true;
+        Ok(())
+    }
+
+    fn expand(&mut self) -> Result<()> {
+        let src_num_units = self.num_units;
+        let src_num_blocks = self.num_blocks();
+        let dest_num_units = src_num_units + BLOCK_SIZE;
+        if dest_num_units > u32::MAX as usize {
+            return Err(Error::TooManyUnits);
+        }
+
+        if src_num_blocks >= NUM_EXTRA_BLOCKS {
+            let block = src_num_blocks - NUM_EXTRA_BLOCKS;
+            self.fix_block(block)?;
+            self.sink.retire(block)?;
+        }
+        self.sink.grow()?;
+        self.num_units = dest_num_units;
+
+        for id in src_num_units..dest_num_units {
+            *self.extra(id) = Extra {
+                prev: id.wrapping_sub(1),
+                next: id + 1,
+                is_fixed: false,
+                is_used: false,
+            };
+        }
+        let head = self.extras_head;
+        if head < src_num_units {
+            let tail = self.extra(head).prev;
+            self.extra(src_num_units).prev = tail;
+            self.extra(dest_num_units - 1).next = head;
+            self.extra(tail).next = src_num_units;
+            self.extra(head).prev = dest_num_units - 1;
+        } else {
+            self.extra(src_num_units).prev = dest_num_units - 1;
+            self.extra(dest_num_units - 1).next = src_num_units;
+        }
+        Ok(())
+    }
+
+    fn fix_block(&mut self, block: usize) -> Result<()> {
+        let begin = block * BLOCK_SIZE;
+        let end = begin + BLOCK_SIZE;
+
+        let unused_offset = (begin..end)
+            .find(|&offset| !self.extra(offset).is_used)
+            .unwrap_or(0);
+        for id in begin..end {
+            if !self.extra(id).is_fixed {
+                self.reserve(id)?;
+                self.sink
+                    .set(id, ((id ^ unused_offset) & LOWER_MASK) as u32)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -14,7 +14,7 @@
 //! ```ignore
 //! use darts::DartsArrayTrie;
 //!
-//! let dic = DartsArrayTrie::new();
+//! let mut dic = DartsArrayTrie::new();
 //! let keys: Vec<String> = todo!() // get keys somehow
 //! let values: Vec<usize> = todo!() // get values somehow
 //! let lengths: Vec<i32> = todo!() // get lengths somehow
@@ -23,26 +23,228 @@
 //! assert_eq!(Ok(()), result);
 //! ```
 //!
+//! ## Build trie with unsigned values
+//!
+//! ```ignore
+//! use darts::darts::{DoubleArrayTrie, MAX_VALUE};
+//!
+//! // Values are stored in 31 bits, so values above MAX_VALUE are rejected.
+//! let mut dic = DoubleArrayTrie::new();
+//! dic.build_u32(&keys, None, &values, None)?;
+//! let value: Option<u32> = dic.exact_match_search_u32("key", 0, 0);
+//! ```
+//!
+//! ## Build trie incrementally
+//!
+//! ```ignore
+//! use darts::builder::TrieBuilder;
+//!
+//! let mut builder = TrieBuilder::new();
+//! builder.insert("apple", 1)?;
+//! builder.extend([("banana", 2), ("cherry", 3)])?;
+//! let dic = builder.finish()?;
+//! ```
+//!
+//! ## Build trie from unsorted keys
+//!
+//! ```ignore
+//! use darts::builder::{DuplicatePolicy, SortingBuilder};
+//!
+//! let mut builder = SortingBuilder::new().duplicate_policy(DuplicatePolicy::KeepLast);
+//! builder.extend([("banana", 1), ("apple", 2), ("banana", 3)]);
+//! let (dic, report) = builder.build()?;
+//! assert_eq!(report.num_duplicates, 1);
+//! ```
+//!
+//! ## Build trie from text files
+//!
+//! ```ignore
+//! use darts::{darts::DoubleArrayTrie, text::TextFormat};
+//!
+//! let mut dic = DoubleArrayTrie::new();
+//! let file = BufReader::new(File::open("words.csv")?);
+//! dic.build_from_reader(file, &TextFormat::csv(0, Some(2)).header(true))?;
+//! ```
+//!
+//! ## Build trie larger than memory
+//!
+//! ```ignore
+//! use darts::external::ExternalBuilder;
+//!
+//! let mut builder = ExternalBuilder::new().memory_limit(64 << 20);
+//! for (key, value) in pairs {
+//!     builder.push(key, value)?;
+//! }
+//! builder.finish("path/to/dict")?;
+//! ```
+//!
+//! ## Build trie on several threads
+//!
+//! ```ignore
+//! use darts::parallel::ParallelBuilder;
+//!
+//! let dic = ParallelBuilder::new().num_threads(8).build(&keys, Some(&values))?;
+//! ```
+//!
+//! ## Insert keys into a built dictionary
+//!
+//! ```ignore
+//! use darts::dynamic::DynamicTrie;
+//!
+//! // Children of a node are moved to free units when a new key needs theirs.
+//! let mut dynamic = DynamicTrie::from_trie(dic);
+//! dynamic.insert("apple", 10)?;
+//! let dic = dynamic.into_trie();
+//! ```
+//!
+//! ## Edit a dictionary
+//!
+//! ```ignore
+//! use darts::delta::DeltaTrie;
+//!
+//! // Edits are searched along with the array, which is rebuilt on another
+//! // thread every 1000 edits.
+//! let mut dic = DeltaTrie::new(dic).threshold(1000);
+//! dic.insert("apple", 10)?;
+//! dic.remove("banana");
+//! assert_eq!(dic.exact_match_search("apple"), Some(10));
+//! ```
+//!
+//! ## Merge dictionaries
+//!
+//! ```ignore
+//! use darts::merge::MergePolicy;
+//!
+//! // Keys of both dictionaries, with the user value of keys found in both.
+//! let (dic, report) = DoubleArrayTrie::merge(&system, &user, MergePolicy::PreferRight)?;
+//! let (dic, report) = DoubleArrayTrie::merge(&system, &user, MergePolicy::Combine(Box::new(|a, b| a.max(b))))?;
+//! ```
+//!
 //! ## Save and open
 //!
 //! ```ignore
 //! use darts::DartsArrayTrie;
 //!
-//! let dic = DartsArrayTrie::new();
+//! let mut dic = DartsArrayTrie::new();
 //! // build ...
-//! let dic_copy = DartsArrayTrie::new();
+//! let mut dic_copy = DartsArrayTrie::new();
 //! assert_eq!(Ok(), dic.save("path/to/dict", "wb", 0));
 //! assert_eq!(Ok(), dic_copy.open("path/to/dict", "rb", 0, 0));
 //!
 //! ```
 //!
+//! ## Save and open with a header
+//!
+//! ```ignore
+//! use darts::{darts::DoubleArrayTrie, header::FLAG_INDEX_VALUES};
+//!
+//! let header = dic.save_with_header("path/to/dict", FLAG_INDEX_VALUES)?;
+//! // Fails if the file is truncated, corrupted or not in this format.
+//! let header = dic_copy.open_with_header("path/to/dict", 0)?;
+//! // Also accepts files written by `save`.
+//! let header = dic_copy.open_any("path/to/dict", 0)?;
+//! ```
+//!
+//! ## Save and open compressed
+//!
+//! With the feature `zstd` or `lz4` enabled:
+//!
+//! ```ignore
+//! use darts::{compress::Zstd, darts::DoubleArrayTrie};
+//!
+//! dic.save_compressed("path/to/dict.zst", &Zstd::default(), 0)?;
+//! // Decompresses into an array owned by the dictionary.
+//! let header = dic_copy.open_compressed("path/to/dict.zst")?;
+//! // Also accepts compressed files.
+//! let header = dic_copy.open_any("path/to/dict.zst", 0)?;
+//! ```
+//!
+//! ## Replace a dictionary while it is searched
+//!
+//! ```ignore
+//! use darts::shared::SharedTrie;
+//!
+//! let shared = Arc::new(SharedTrie::new(dic));
+//! // Searches in flight finish on the dictionary they loaded.
+//! let value = shared.load().exact_match_search("key", 3, 0);
+//! shared.reload("path/to/new/dict")?;
+//!
+//! // Reloads the file whenever it changes, if `load` succeeds.
+//! let watcher = shared.watch("path/to/dict", Duration::from_secs(10), |path| {
+//!     let mut dic = DoubleArrayTrie::new();
+//!     dic.open_with_header(path.to_str().unwrap(), 0)?;
+//!     Ok(dic)
+//! });
+//! ```
+//!
+//! ## Ownership of the units
+//!
+//! A dictionary owns, borrows or maps its array of units, and units moved in
+//! or borrowed are checked before they are searched:
+//!
+//! ```ignore
+//! use darts::darts::{DoubleArrayTrie, Ownership};
+//!
+//! let units: Vec<u32> = dic.into_units();
+//! let dic = DoubleArrayTrie::from_units(units)?;
+//! assert_eq!(dic.ownership(), Ownership::Owned);
+//!
+//! // Searches the units in place for as long as they are borrowed.
+//! let dic_ref = DoubleArrayTrie::from_slice(dic.array())?;
+//! assert_eq!(dic_ref.ownership(), Ownership::Borrowed);
+//! ```
+//!
+//! ## Serde
+//!
+//! With the feature `serde` enabled, a dictionary is serialized as the bytes
+//! of its units:
+//!
+//! ```ignore
+//! let bytes = bincode::serialize(&dic)?;
+//! let dic_copy: DoubleArrayTrie = bincode::deserialize(&bytes)?;
+//! ```
+//!
+//! ## Zero-copy archives
+//!
+//! With the feature `rkyv` enabled, archived dictionaries are searched in
+//! place:
+//!
+//! ```ignore
+//! #[derive(rkyv::Archive, rkyv::Serialize)]
+//! struct Bundle {
+//!     dic: DoubleArrayTrie,
+//!     labels: Vec<String>,
+//! }
+//!
+//! let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&bundle)?;
+//! let archived = rkyv::access::<ArchivedBundle, rkyv::rancor::Error>(&bytes)?;
+//! let value = archived.dic.as_trie().exact_match_search("key", 3, 0);
+//! let label = &archived.labels[value as usize];
+//! ```
+//!
+//! ## Archives
+//!
+//! ```ignore
+//! use darts::archive::{Archive, ArchiveWriter};
+//!
+//! let mut writer = ArchiveWriter::new();
+//! writer.add_dictionary("words", &words, 0);
+//! writer.add_table("labels", &labels);
+//! writer.write("path/to/archive")?;
+//!
+//! // Dictionaries of a mapped archive refer to the mapped units.
+//! let archive = Archive::map("path/to/archive")?;
+//! let words = archive.dictionary("words")?;
+//! let labels = archive.table("labels")?;
+//! ```
+//!
 //! ## Search
 //!
 //! #### Exact match search
 //! ```ignore
 //! use darts::DartsArrayTrie;
 //!
-//! let dic = DartArrayTrie::new();
+//! let mut dic = DartArrayTrie::new();
 //! // build ...
 //! let value = dic.extra_match_search(key, 0, 0);
 //! assert_eq!(value, /* expected value */);
@@ -57,7 +259,7 @@
 //! use darts::DartsArrayTrie;
 //!
 //! const MAX_RESULT_NUM: usize = 16;
-//! let dic = DartsArrayTrie::new();
+//! let mut dic = DartsArrayTrie::new();
 //! // build ...
 //! let results = dic.common_prefix_search(key, MAX_RESULT_NUM, 0, 0);
 //! assert_eq!(results, /* expected results */);
@@ -67,7 +269,7 @@
 //! ```ignore
 //! use darts::DartsArrayTrie;
 //!
-//! let dic = DartArrayTrie::new();
+//! let mut dic = DartArrayTrie::new();
 //! // build ...
 //! let value = dic.common_longest_prefix_search(key, 0, 0);
 //! assert_eq!(value, /* expected value */);
@@ -82,7 +284,7 @@
 //! ```ignore
 //! use darts::DartsArrayTrie;
 //!
-//! let dic = DartsArrayTrie::new();
+//! let mut dic = DartsArrayTrie::new();
 //! // build ...
 //! let mut id = 0usize;
 //! let mut key_pos = 0usize;
@@ -91,8 +293,84 @@
 //!     assert_ne!(result, -2);
 //! }
 //! ```
+//!
+//! ## Key-only sets
+//!
+//! ```ignore
+//! use darts::set::TrieSet;
+//!
+//! let stopwords: TrieSet = ["a", "an", "the"].into_iter().collect();
+//! assert!(stopwords.contains("an"));
+//! assert!(stopwords.contains_prefix_of("theory"));
+//! ```
+//!
+//! ## Statistics
+//!
+//! ```ignore
+//! let stats = dic.stats();
+//! println!("{} keys, {:.1}% of units used", stats.num_keys, stats.fill_ratio * 100.0);
+//! println!("{}", stats);
+//! ```
+//!
+//! ## Graphviz
+//!
+//! ```ignore
+//! std::fs::write("dict.dot", dic.to_dot())?;
+//! // Only the nodes within 2 transitions of the node reached by "ab".
+//! let mut node_pos = 0;
+//! dic.traverse("ab", &mut node_pos, &mut 0, 2);
+//! std::fs::write("ab.dot", dic.to_dot_from(node_pos, Some(2)))?;
+//! ```
+//!
+//! ## Search several dictionaries
+//!
+//! ```ignore
+//! use darts::overlay::Overlay;
+//!
+//! // Keys of the user hide the same keys of the team, which hide the system ones.
+//! let overlay = Overlay::new()
+//!     .layer(&user)
+//!     .layer_with_tombstones(&team, &removed_by_team)
+//!     .layer(&system);
+//! let hit = overlay.common_longest_prefix_search("key")?;
+//! println!("{} from layer {}", hit.value, hit.layer);
+//! ```
+//!
+//! ## Map keys to IDs and back
+//!
+//! ```ignore
+//! use darts::perfect_hash::PerfectHash;
+//!
+//! // IDs from 0 to the number of keys - 1, in key order.
+//! let hash = PerfectHash::new(dic)?;
+//! let id = hash.id_of("apple").unwrap();
+//! assert_eq!(hash.key_of(id).unwrap(), b"apple");
+//! ```
 
+pub mod archive;
+pub mod builder;
+pub mod compress;
 pub mod darts;
+pub mod delta;
+mod dot;
+pub mod dynamic;
+pub mod error;
+pub mod external;
+pub mod header;
+mod layout;
+pub mod merge;
+pub mod overlay;
+pub mod parallel;
+pub mod perfect_hash;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod set;
+pub mod shared;
+pub mod stats;
+pub mod text;
+mod unit;
+#[cfg(feature = "rkyv")]
+pub mod zero_copy;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,76 @@
+//! Module for merging dictionaries.
+
+use std::cmp::Ordering;
+
+use crate::{
+    builder::{BuildReport, TrieBuilder},
+    darts::DoubleArrayTrie,
+    error::Result,
+};
+
+/// Decides which value a key found in both merged dictionaries gets.
+#[derive(Default)]
+pub enum MergePolicy {
+    /// Keeps the value of the left dictionary.
+    #[default]
+    PreferLeft,
+    /// Keeps the value of the right dictionary.
+    PreferRight,
+    /// Combines the values. The function is called with the value of the
+    /// left dictionary and the value of the right dictionary.
+    Combine(Box<dyn FnMut(i32, i32) -> i32>),
+}
+
+impl DoubleArrayTrie {
+    /// Constructs a dictionary holding the keys of both `left` and `right`,
+    /// resolving keys found in both with `policy`. The keys are enumerated
+    /// from the arrays, so the files they were built from are not needed.
+    ///
+    /// The duplicates of the report are the keys found in both dictionaries.
+    ///
+    /// ```ignore
+    /// use darts::merge::MergePolicy;
+    ///
+    /// let (dic, report) = DoubleArrayTrie::merge(&system, &user, MergePolicy::PreferRight)?;
+    /// ```
+    pub fn merge(
+        left: &DoubleArrayTrie,
+        right: &DoubleArrayTrie,
+        mut policy: MergePolicy,
+    ) -> Result<(DoubleArrayTrie, BuildReport)> {
+        let mut builder = TrieBuilder::new();
+        let mut num_duplicates = 0;
+        let mut left = left.iter().peekable();
+        let mut right = right.iter().peekable();
+        loop {
+            let order = match (left.peek(), right.peek()) {
+                (Some((left_key, _)), Some((right_key, _))) => left_key.cmp(right_key),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            let (key, value) = match order {
+                Ordering::Less => left.next().unwrap(),
+                Ordering::Greater => right.next().unwrap(),
+                Ordering::Equal => {
+                    let (key, left_value) = left.next().unwrap();
+                    let (_, right_value) = right.next().unwrap();
+                    num_duplicates += 1;
+                    let value = match policy {
+                        MergePolicy::PreferLeft => left_value,
+                        MergePolicy::PreferRight => right_value,
+                        MergePolicy::Combine(ref mut combine) => combine(left_value, right_value),
+                    };
+                    (key, value)
+                }
+            };
+            builder.insert(key, value)?;
+        }
+
+        let report = BuildReport {
+            num_keys: builder.len(),
+            num_duplicates,
+        };
+        Ok((builder.finish()?, report))
+    }
+}
@@ -0,0 +1,157 @@
+//! Module for searching several dictionaries as one.
+
+use std::collections::HashSet;
+
+use crate::{darts::DoubleArrayTrie, set::TrieSet};
+
+/// Key found by an [`Overlay`], with the layer it was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayHit {
+    /// Index of the layer, 0 being the top layer.
+    pub layer: usize,
+    pub value: i32,
+    /// Length of the key in bytes.
+    pub length: usize,
+}
+
+struct Layer<'a> {
+    dic: &'a DoubleArrayTrie,
+    tombstones: Option<&'a TrieSet>,
+}
+
+/// Stack of dictionaries searched from the top layer down, e.g. the
+/// dictionary of a user over the one of a team over the system dictionary.
+///
+/// A key found in a layer hides the same key in the layers below it, and a
+/// layer can also hide keys of the layers below it without having a value
+/// for them, with a set of tombstones.
+///
+/// ```ignore
+/// use darts::overlay::Overlay;
+///
+/// let overlay = Overlay::new()
+///     .layer(&user)
+///     .layer_with_tombstones(&team, &removed_by_team)
+///     .layer(&system);
+/// if let Some(hit) = overlay.exact_match_search("key") {
+///     println!("{} from layer {}", hit.value, hit.layer);
+/// }
+/// ```
+#[derive(Default)]
+pub struct Overlay<'a> {
+    layers: Vec<Layer<'a>>,
+}
+
+impl<'a> Overlay<'a> {
+    /// Constructs an overlay without layers.
+    pub fn new() -> Overlay<'a> {
+        Overlay::default()
+    }
+
+    /// Adds a layer below the layers added so far.
+    pub fn layer(mut self, dic: &'a DoubleArrayTrie) -> Overlay<'a> {
+        self.layers.push(Layer {
+            dic,
+            tombstones: None,
+        });
+        self
+    }
+
+    /// Adds a layer below the layers added so far, which hides the keys in
+    /// `tombstones` from the layers below it.
+    pub fn layer_with_tombstones(
+        mut self,
+        dic: &'a DoubleArrayTrie,
+        tombstones: &'a TrieSet,
+    ) -> Overlay<'a> {
+        self.layers.push(Layer {
+            dic,
+            tombstones: Some(tombstones),
+        });
+        self
+    }
+
+    /// Returns the number of layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Tests whether the overlay has no layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Searches for `key` from the top layer down and returns the first hit,
+    /// unless a tombstone above it hides the key.
+    pub fn exact_match_search(&self, key: &str) -> Option<OverlayHit> {
+        if key.is_empty() || key.contains('\0') {
+            return None;
+        }
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !layer.dic.array().is_empty() {
+                let value = layer.dic.exact_match_search(key, key.len(), 0);
+                if value >= 0 {
+                    return Some(OverlayHit {
+                        layer: index,
+                        value,
+                        length: key.len(),
+                    });
+                }
+            }
+            if layer
+                .tombstones
+                .is_some_and(|tombstones| tombstones.contains(key))
+            {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Searches for keys which match a prefix of `text` and returns the
+    /// visible hit of each length, in ascending order of length. The hit of
+    /// a length is the one of the highest layer having the key, unless a
+    /// tombstone above it hides the key.
+    pub fn common_prefix_search(&self, text: &str) -> Vec<OverlayHit> {
+        // Keys can not contain a null character.
+        let text = text.split('\0').next().unwrap();
+        let mut hits = Vec::new();
+        if text.is_empty() {
+            return hits;
+        }
+        // Lengths found or hidden by a higher layer.
+        let mut resolved = HashSet::new();
+        // A text has at most one prefix of each length.
+        let max_num_results = text.len();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !layer.dic.array().is_empty() {
+                for result in layer
+                    .dic
+                    .common_prefix_search(text, max_num_results, text.len(), 0)
+                {
+                    if resolved.insert(result.length) {
+                        hits.push(OverlayHit {
+                            layer: index,
+                            value: result.value,
+                            length: result.length,
+                        });
+                    }
+                }
+            }
+            if let Some(tombstones) = layer.tombstones.filter(|tombstones| !tombstones.is_empty()) {
+                let trie = tombstones.as_trie();
+                for result in trie.common_prefix_search(text, max_num_results, text.len(), 0) {
+                    resolved.insert(result.length);
+                }
+            }
+        }
+        hits.sort_by_key(|hit| hit.length);
+        hits
+    }
+
+    /// Searches for the longest visible key which matches a prefix of `text`,
+    /// as found by [`Overlay::common_prefix_search`].
+    pub fn common_longest_prefix_search(&self, text: &str) -> Option<OverlayHit> {
+        self.common_prefix_search(text).pop()
+    }
+}
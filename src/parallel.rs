@@ -0,0 +1,279 @@
+//! Module for building dictionaries on several threads.
+//!
+//! The keys are partitioned by their first byte and the nodes below each
+//! first byte are arranged on a thread pool, each partition in its own array
+//! whose first two blocks are left empty. The arrays are then moved to
+//! positions aligned to a power of two not smaller than their size, which
+//! keeps the relative offsets valid because the units are addressed with
+//! exclusive or. Finally, the root is written in the first block and the
+//! nodes of the first bytes in the second block.
+
+use std::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    builder::check_pair,
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    layout::{node_unit, Arranger, BLOCK_SIZE},
+    unit,
+};
+
+/// Offset of the root, i.e. the node of the first byte `c` is at `ROOT_OFFSET ^ c`.
+const ROOT_OFFSET: usize = BLOCK_SIZE;
+const NUM_FIXED_UNITS: usize = 2 * BLOCK_SIZE;
+
+/// Builder which constructs a dictionary from sorted keys on several threads.
+///
+/// The resultant dictionary answers every search exactly like one built by
+/// [`DoubleArrayTrie::build`] from the same keys, although the units differ.
+/// It is always a trie, so it can be larger than a DAWG built with values.
+///
+/// ```ignore
+/// use darts::parallel::ParallelBuilder;
+///
+/// let dic = ParallelBuilder::new().num_threads(8).build(&keys, Some(&values))?;
+/// ```
+pub struct ParallelBuilder {
+    num_threads: usize,
+}
+
+/// Keys sharing the same first byte.
+struct Partition {
+    label: u8,
+    begin: usize,
+    end: usize,
+}
+
+impl ParallelBuilder {
+    /// Constructs a builder which uses as many threads as the available
+    /// parallelism.
+    pub fn new() -> ParallelBuilder {
+        ParallelBuilder {
+            num_threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    /// Sets the number of threads. 0 is treated as 1.
+    pub fn num_threads(mut self, num_threads: usize) -> ParallelBuilder {
+        self.num_threads = num_threads.max(1);
+        self
+    }
+
+    /// Constructs a dictionary from keys arranged in key order. If `values`
+    /// is [`None`], the index in `keys` is associated with each key. As with
+    /// [`DoubleArrayTrie::build`], only the first pair of duplicate keys is
+    /// stored.
+    ///
+    /// If `values` and `keys` have different lengths, returns
+    /// [`Error::CountMismatch`].
+    pub fn build<K>(&self, keys: &[K], values: Option<&[i32]>) -> Result<DoubleArrayTrie>
+    where
+        K: AsRef<[u8]> + Sync,
+    {
+        if let Some(values) = values.filter(|values| values.len() != keys.len()) {
+            return Err(Error::CountMismatch {
+                what: "values",
+                count: values.len(),
+                num_keys: keys.len(),
+            });
+        }
+        let partitions = partition(keys)?;
+        if partitions.is_empty() {
+            let (units, _) = Arranger::new(Vec::new())?.finish()?;
+            return Ok(DoubleArrayTrie::from_valid_units(units));
+        }
+
+        // The largest partitions go first so that no thread is left with a
+        // large one at the end.
+        let mut order = (0..partitions.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| {
+            std::cmp::Reverse(partitions[index].end - partitions[index].begin)
+        });
+
+        let next = AtomicUsize::new(0);
+        let num_threads = self.num_threads.min(partitions.len());
+        let mut results = thread::scope(|scope| {
+            let workers = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = match order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                                Some(&index) => index,
+                                None => break results,
+                            };
+                            results
+                                .push((index, arrange_partition(keys, values, &partitions[index])));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Reports the error of the smallest index, as a sequential build would.
+        results.sort_by_key(|&(index, _)| index);
+        let arrays = results
+            .into_iter()
+            .map(|(_, result)| result)
+            .collect::<Result<Vec<_>>>()?;
+        merge(&partitions, arrays).map(DoubleArrayTrie::from_valid_units)
+    }
+}
+
+impl Default for ParallelBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits keys by their first byte. The keys are only fully checked when
+/// their partition is arranged.
+fn partition<K: AsRef<[u8]>>(keys: &[K]) -> Result<Vec<Partition>> {
+    let mut partitions: Vec<Partition> = Vec::new();
+    for (index, key) in keys.iter().enumerate() {
+        let key = key.as_ref();
+        let label = match key.first() {
+            Some(&label) if label != 0 => label,
+            _ => {
+                return Err(Error::InvalidKey {
+                    index,
+                    key: key.to_vec(),
+                })
+            }
+        };
+        match partitions.last_mut() {
+            Some(last) if last.label == label => last.end = index + 1,
+            Some(last) if last.label > label => {
+                return Err(Error::WrongKeyOrder {
+                    index,
+                    key: key.to_vec(),
+                })
+            }
+            _ => partitions.push(Partition {
+                label,
+                begin: index,
+                end: index + 1,
+            }),
+        }
+    }
+    Ok(partitions)
+}
+
+/// Arranges the nodes below the first byte of a partition. The node of the
+/// first byte is at the same position as in the merged array.
+fn arrange_partition<K: AsRef<[u8]>>(
+    keys: &[K],
+    values: Option<&[i32]>,
+    partition: &Partition,
+) -> Result<Vec<u32>> {
+    let mut pairs: Vec<(&[u8], i32)> = Vec::with_capacity(partition.end - partition.begin);
+    for index in partition.begin..partition.end {
+        let key = keys[index].as_ref();
+        let value = values.map_or(index as i32, |values| values[index]);
+        check_pair(index, key, value)?;
+        match pairs.last() {
+            Some(&(last, _)) if key == last => continue,
+            Some(&(last, _)) if key < last => {
+                return Err(Error::WrongKeyOrder {
+                    index,
+                    key: key.to_vec(),
+                })
+            }
+            _ => pairs.push((key, value)),
+        }
+    }
+
+    let mut arranger = Arranger::with_fixed_blocks(Vec::new(), NUM_FIXED_UNITS / BLOCK_SIZE)?;
+    let root = ROOT_OFFSET ^ partition.label as usize;
+    // Ranges of keys sharing a prefix of `depth` bytes, with their node.
+    let mut stack = vec![(root, partition.label, 0, pairs.len(), 1)];
+    let mut labels = Vec::with_capacity(256);
+    let mut children = Vec::with_capacity(256);
+    while let Some((id, label, begin, end, depth)) = stack.pop() {
+        labels.clear();
+        children.clear();
+        let mut value = 0;
+        for (index, &(key, key_value)) in pairs.iter().enumerate().take(end).skip(begin) {
+            match key.get(depth) {
+                None => {
+                    labels.push(0);
+                    value = key_value;
+                }
+                Some(&child_label) if labels.last() == Some(&child_label) => {}
+                Some(&child_label) => {
+                    labels.push(child_label);
+                    children.push((child_label, index));
+                }
+            }
+        }
+
+        let offset = arranger.arrange(id, label, &labels, value)?;
+        let mut child_end = end;
+        for &(child_label, child_begin) in children.iter().rev() {
+            stack.push((
+                offset ^ child_label as usize,
+                child_label,
+                child_begin,
+                child_end,
+                depth + 1,
+            ));
+            child_end = child_begin;
+        }
+    }
+    let (units, _) = arranger.finish()?;
+    Ok(units)
+}
+
+/// Moves the arrays of the partitions into a single array and links them to
+/// the root.
+fn merge(partitions: &[Partition], arrays: Vec<Vec<u32>>) -> Result<Vec<u32>> {
+    // Places the largest arrays first, each at the lowest free position which
+    // is a multiple of its size rounded up to a power of two.
+    let mut order = (0..arrays.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| std::cmp::Reverse(arrays[index].len()));
+    let mut bases = vec![0; arrays.len()];
+    let mut used = vec![(0, NUM_FIXED_UNITS)];
+    for &index in &order {
+        let len = arrays[index].len();
+        let align = len.next_power_of_two();
+        let mut base = align;
+        while used
+            .iter()
+            .any(|&(begin, end)| base < end && begin < base + len)
+        {
+            base += align;
+        }
+        bases[index] = base;
+        used.push((base, base + len));
+    }
+    let num_units = used.iter().map(|&(_, end)| end).max().unwrap();
+    if num_units > u32::MAX as usize {
+        return Err(Error::TooManyUnits);
+    }
+
+    let mut units = vec![0; num_units];
+    // The fixed blocks are filled like unused units, so that their labels
+    // never match the label of a missing child.
+    for (id, unit) in units.iter_mut().enumerate().take(NUM_FIXED_UNITS) {
+        let unused_offset = if id < BLOCK_SIZE { 0 } else { ROOT_OFFSET + 1 };
+        *unit = ((id ^ unused_offset) & 0xFF) as u32;
+    }
+    units[0] = node_unit(0, false, ROOT_OFFSET).unwrap();
+    for ((partition, array), &base) in partitions.iter().zip(&arrays).zip(&bases) {
+        units[base + NUM_FIXED_UNITS..base + array.len()]
+            .copy_from_slice(&array[NUM_FIXED_UNITS..]);
+        let id = ROOT_OFFSET ^ partition.label as usize;
+        let has_leaf = unit::has_leaf(array[id]);
+        let offset = unit::offset(array[id]) as usize ^ base;
+        units[id] = node_unit(partition.label, has_leaf, offset).ok_or(Error::TooManyUnits)?;
+    }
+    Ok(units)
+}
@@ -0,0 +1,115 @@
+//! Module for mapping keys to dense IDs and back.
+
+use crate::{
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    unit,
+};
+
+/// Minimal perfect hash of the keys of a dictionary, which maps the keys to
+/// IDs from 0 to `len() - 1` in byte order and the IDs back to the keys.
+///
+/// The values of the dictionary are not used. Instead, a side array holds
+/// for each node the number of keys before the ones below it, so that the
+/// ID of a key is read at its last node and the key of an ID is found by
+/// going down to the child whose keys include it.
+///
+/// ```ignore
+/// use darts::perfect_hash::PerfectHash;
+///
+/// let mut dic = DoubleArrayTrie::new();
+/// dic.build(words.len(), &words, None, None, None)?;
+/// let hash = PerfectHash::new(dic)?;
+/// let id = hash.id_of("apple").unwrap();
+/// assert_eq!(hash.key_of(id).unwrap(), b"apple");
+/// ```
+pub struct PerfectHash {
+    dic: DoubleArrayTrie,
+    ranks: Vec<u32>,
+    len: usize,
+}
+
+impl PerfectHash {
+    /// Counts the keys of `dic` below each node. Fails with
+    /// [`Error::InvalidFormat`] if nodes are shared, as in a DAWG, because
+    /// their keys do not have the same IDs along each path.
+    pub fn new(dic: DoubleArrayTrie) -> Result<PerfectHash> {
+        let units = dic.array();
+        let mut ranks = vec![0; units.len()];
+        let mut len = 0;
+        if !units.is_empty() {
+            // Visits the nodes in key order, a key coming before the keys it
+            // is a prefix of.
+            let mut visited = vec![false; units.len()];
+            let mut stack = vec![0];
+            while let Some(id) = stack.pop() {
+                if visited[id] {
+                    return Err(Error::InvalidFormat(format!(
+                        "node {} is shared by several keys",
+                        id
+                    )));
+                }
+                visited[id] = true;
+                ranks[id] = len as u32;
+                if unit::has_leaf(units[id]) {
+                    len += 1;
+                }
+                let num_children = stack.len();
+                stack.extend(unit::children(units, id).map(|(_, child)| child));
+                stack[num_children..].reverse();
+            }
+        }
+        Ok(PerfectHash { dic, ranks, len })
+    }
+
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests whether there are no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the ID of `key`, i.e. the number of keys before it in byte
+    /// order, if it exists.
+    pub fn id_of(&self, key: &str) -> Option<usize> {
+        let units = self.dic.array();
+        if units.is_empty() || key.is_empty() {
+            return None;
+        }
+        let mut id = 0;
+        for &label in key.as_bytes() {
+            if label == 0 {
+                return None;
+            }
+            id = unit::child(units, id, label)?;
+        }
+        unit::has_leaf(units[id]).then(|| self.ranks[id] as usize)
+    }
+
+    /// Returns the key whose ID is `id`, if `id` is less than
+    /// [`PerfectHash::len`].
+    pub fn key_of(&self, id: usize) -> Option<Vec<u8>> {
+        if id >= self.len {
+            return None;
+        }
+        let units = self.dic.array();
+        let mut key = Vec::new();
+        let mut node = 0;
+        while !(unit::has_leaf(units[node]) && self.ranks[node] as usize == id) {
+            let (label, child) = unit::children(units, node)
+                .take_while(|&(_, child)| self.ranks[child] as usize <= id)
+                .last()?;
+            key.push(label);
+            node = child;
+        }
+        Some(key)
+    }
+
+    /// Returns the dictionary of the keys.
+    pub fn as_trie(&self) -> &DoubleArrayTrie {
+        &self.dic
+    }
+}
@@ -0,0 +1,63 @@
+//! Serde support, enabled by the feature `serde`.
+//!
+//! A dictionary is serialized as the bytes of its array of units in
+//! little-endian order, as in files, so that binary formats store it as one
+//! block. A dictionary without an array is serialized as no bytes.
+
+use std::fmt;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{darts::DoubleArrayTrie, header::ByteOrder};
+
+impl Serialize for DoubleArrayTrie {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self
+            .array()
+            .iter()
+            .flat_map(|&unit| ByteOrder::Little.encode(unit))
+            .collect::<Vec<_>>();
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for DoubleArrayTrie {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(UnitsVisitor)
+    }
+}
+
+struct UnitsVisitor;
+
+impl UnitsVisitor {
+    fn units<E: de::Error>(bytes: &[u8]) -> Result<DoubleArrayTrie, E> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err(E::invalid_length(bytes.len(), &UnitsVisitor));
+        }
+        DoubleArrayTrie::from_units(ByteOrder::Little.decode(bytes)).map_err(E::custom)
+    }
+}
+
+impl<'de> Visitor<'de> for UnitsVisitor {
+    type Value = DoubleArrayTrie;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("the bytes of an array of units")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        UnitsVisitor::units(bytes)
+    }
+
+    // Formats without a type for bytes, e.g. JSON, write them as a sequence.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        UnitsVisitor::units(&bytes)
+    }
+}
@@ -0,0 +1,199 @@
+//! Module for key-only dictionaries.
+
+use std::{cmp::Ordering, collections::BTreeSet};
+
+use crate::{
+    builder::check_pair,
+    darts::{self, DoubleArrayTrie},
+    error::{Error, Result},
+};
+
+/// Type of set of keys backed by a double array trie.
+///
+/// It is useful for dictionaries which only need to answer whether a key
+/// exists or not, e.g. stopwords or blocklists. Note that empty keys and keys
+/// containing a null character can not be stored.
+pub struct TrieSet {
+    trie: DoubleArrayTrie,
+    len: usize,
+}
+
+/// Iterator over the keys of a [`TrieSet`] in byte order.
+pub struct Iter<'a> {
+    inner: darts::Iter<'a>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(key, _)| String::from_utf8_lossy(&key).into_owned())
+    }
+}
+
+impl TrieSet {
+    /// Constructs an empty set.
+    pub fn new() -> TrieSet {
+        TrieSet {
+            trie: DoubleArrayTrie::new(),
+            len: 0,
+        }
+    }
+
+    /// Constructs a set from keys in arbitrary order. Duplicate keys are stored
+    /// only once. Returns [`Error::InvalidKey`] if a key is empty or contains
+    /// a null character.
+    pub fn build<I, S>(keys: I) -> Result<TrieSet>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let keys = keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let key = key.as_ref();
+                check_pair(index, key.as_bytes(), 0)?;
+                Ok(key.to_owned())
+            })
+            .collect::<Result<BTreeSet<_>>>()?
+            .into_iter()
+            .collect::<Vec<_>>();
+        Self::from_sorted(keys)
+    }
+
+    fn from_sorted(keys: Vec<String>) -> Result<TrieSet> {
+        let mut trie = DoubleArrayTrie::new();
+        if !keys.is_empty() {
+            trie.build(keys.len(), &keys, None, None, None)
+                .map_err(|what| Error::Darts(what.to_owned()))?;
+        }
+        Ok(TrieSet {
+            trie,
+            len: keys.len(),
+        })
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests whether the set has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Tests whether the given key exists in the set.
+    pub fn contains(&self, key: &str) -> bool {
+        !self.is_empty()
+            && !key.is_empty()
+            && !key.contains('\0')
+            && self.trie.exact_match_search(key, key.len(), 0) >= 0
+    }
+
+    /// Tests whether any key in the set is a prefix of `text`, including
+    /// `text` itself.
+    pub fn contains_prefix_of(&self, text: &str) -> bool {
+        // Keys can not contain a null character.
+        let text = text.split('\0').next().unwrap();
+        !self.is_empty()
+            && !text.is_empty()
+            && self.trie.common_longest_prefix_search(text, text.len(), 0) >= 0
+    }
+
+    /// Returns an iterator over the keys in byte order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.trie.iter(),
+        }
+    }
+
+    /// Returns the underlying double array trie, e.g. in order to save it.
+    /// The value of each key is its index in byte order.
+    pub fn as_trie(&self) -> &DoubleArrayTrie {
+        &self.trie
+    }
+
+    /// Returns a new set holding the keys in `self` or in `other`.
+    pub fn union(&self, other: &TrieSet) -> TrieSet {
+        self.combine(other, true, true, true)
+    }
+
+    /// Returns a new set holding the keys in both `self` and `other`.
+    pub fn intersection(&self, other: &TrieSet) -> TrieSet {
+        self.combine(other, false, true, false)
+    }
+
+    /// Returns a new set holding the keys in `self` but not in `other`.
+    pub fn difference(&self, other: &TrieSet) -> TrieSet {
+        self.combine(other, true, false, false)
+    }
+
+    /// Merges the sorted keys of both sets, keeping the keys only in `self`,
+    /// the keys in both sets and the keys only in `other` as requested.
+    fn combine(&self, other: &TrieSet, left: bool, both: bool, right: bool) -> TrieSet {
+        let mut keys = Vec::new();
+        let mut a = self.iter().peekable();
+        let mut b = other.iter().peekable();
+        loop {
+            let order = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) => x.as_bytes().cmp(y.as_bytes()),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match order {
+                Ordering::Less => {
+                    let key = a.next().unwrap();
+                    if left {
+                        keys.push(key);
+                    }
+                }
+                Ordering::Greater => {
+                    let key = b.next().unwrap();
+                    if right {
+                        keys.push(key);
+                    }
+                }
+                Ordering::Equal => {
+                    let key = a.next().unwrap();
+                    b.next();
+                    if both {
+                        keys.push(key);
+                    }
+                }
+            }
+        }
+        // The keys come from valid sets, so building them again can not fail.
+        Self::from_sorted(keys).unwrap()
+    }
+}
+
+impl Default for TrieSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> IntoIterator for &'a TrieSet {
+    type Item = String;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Collects keys into a set. Panics if a key can not be stored, see
+/// [`TrieSet::build`] for a fallible alternative.
+impl<S: AsRef<str>> FromIterator<S> for TrieSet {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        match TrieSet::build(iter) {
+            Ok(set) => set,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
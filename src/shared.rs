@@ -0,0 +1,175 @@
+//! Module for dictionaries replaced while they are searched.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+    header::Header,
+};
+
+/// Handle to a dictionary which can be swapped for a new one at any time.
+///
+/// Searches go through the dictionary returned by [`SharedTrie::load`], so
+/// the ones in flight when a new dictionary is stored finish on the old one,
+/// which is freed when the last of them drops it.
+///
+/// ```ignore
+/// use darts::shared::SharedTrie;
+///
+/// let shared = Arc::new(SharedTrie::new(dic));
+/// let value = shared.load().exact_match_search("key", 3, 0);
+/// shared.reload("path/to/new/dict")?;
+/// ```
+pub struct SharedTrie {
+    current: ArcSwap<DoubleArrayTrie>,
+}
+
+impl SharedTrie {
+    /// Constructs a handle to `dic`.
+    pub fn new(dic: DoubleArrayTrie) -> SharedTrie {
+        SharedTrie {
+            current: ArcSwap::from_pointee(dic),
+        }
+    }
+
+    /// Returns the current dictionary. It stays valid after it is replaced.
+    pub fn load(&self) -> Arc<DoubleArrayTrie> {
+        self.current.load_full()
+    }
+
+    /// Replaces the current dictionary with `dic` and returns the old one.
+    pub fn store(&self, dic: DoubleArrayTrie) -> Arc<DoubleArrayTrie> {
+        self.current.swap(Arc::new(dic))
+    }
+
+    /// Reads a dictionary with [`DoubleArrayTrie::open_any`] and stores it.
+    /// The current dictionary is kept if the file can not be read or its
+    /// units refer to units out of the array.
+    pub fn reload(&self, file_name: &str) -> Result<Option<Header>> {
+        let mut dic = DoubleArrayTrie::new();
+        let header = dic.open_any(file_name, 0)?;
+        self.store(dic);
+        Ok(header)
+    }
+
+    /// Starts a thread checking every `interval` whether the file at `path`
+    /// was modified, in which case `load` reads it and the dictionary it
+    /// returns is stored. The thread stops when the [`Watcher`] is dropped.
+    ///
+    /// `load` validates the new dictionary before it is stored, e.g. by
+    /// reading it with [`DoubleArrayTrie::open_with_header`], which checks
+    /// the checksum, and by searching known keys. If it fails, the current
+    /// dictionary is kept and the file is read again at the next check, so a
+    /// file being written is picked up once it is complete. Writing the new
+    /// version to another file and renaming it avoids reading it partially.
+    ///
+    /// ```ignore
+    /// let watcher = shared.watch("path/to/dict", Duration::from_secs(10), |path| {
+    ///     let mut dic = DoubleArrayTrie::new();
+    ///     dic.open_with_header(path.to_str().unwrap(), 0)?;
+    ///     Ok(dic)
+    /// });
+    /// ```
+    pub fn watch<P, F>(self: &Arc<Self>, path: P, interval: Duration, mut load: F) -> Watcher
+    where
+        P: Into<PathBuf>,
+        F: FnMut(&Path) -> Result<DoubleArrayTrie> + Send + 'static,
+    {
+        let path = path.into();
+        let shared = Arc::clone(self);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let state = Arc::new(WatchState::default());
+        let thread_state = Arc::clone(&state);
+
+        let thread = thread::spawn(move || {
+            let mut stamp = Stamp::of(&path);
+            // Dropping the sender wakes the thread up at once.
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let new_stamp = Stamp::of(&path);
+                if new_stamp == stamp || new_stamp.is_none() {
+                    continue;
+                }
+                match load(&path) {
+                    Ok(dic) => {
+                        shared.store(dic);
+                        stamp = new_stamp;
+                        thread_state.num_reloads.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(err) => *thread_state.error.lock().unwrap() = Some(err),
+                }
+            }
+        });
+        Watcher {
+            stop: Some(stop),
+            thread: Some(thread),
+            state,
+        }
+    }
+}
+
+/// Modification time and length of a file, or [`None`] if it is missing.
+#[derive(PartialEq, Eq)]
+struct Stamp(Option<(SystemTime, u64)>);
+
+impl Stamp {
+    fn of(path: &Path) -> Stamp {
+        Stamp(
+            fs::metadata(path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                .ok(),
+        )
+    }
+
+    fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    num_reloads: AtomicUsize,
+    error: Mutex<Option<Error>>,
+}
+
+/// Thread reloading a [`SharedTrie`], started by [`SharedTrie::watch`].
+pub struct Watcher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    state: Arc<WatchState>,
+}
+
+impl Watcher {
+    /// Returns the number of dictionaries stored so far.
+    pub fn num_reloads(&self) -> usize {
+        self.state.num_reloads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the last error of reading or validating the file, if any,
+    /// and forgets it.
+    pub fn take_error(&self) -> Option<Error> {
+        self.state.error.lock().unwrap().take()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            // A panic of `load` has already been reported by the thread.
+            let _ = thread.join();
+        }
+    }
+}
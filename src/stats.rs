@@ -0,0 +1,138 @@
+//! Module for describing the structure of a dictionary.
+
+use std::fmt;
+
+use crate::{darts::DoubleArrayTrie, unit};
+
+/// Structural report of a dictionary, returned by [`DoubleArrayTrie::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Number of keys.
+    pub num_keys: usize,
+    /// Number of units in the array.
+    pub num_units: usize,
+    /// Number of units holding a value.
+    pub num_leaf_units: usize,
+    /// Number of units of nodes, including the root.
+    pub num_internal_units: usize,
+    /// Number of units which are not used.
+    pub num_empty_units: usize,
+    /// Ratio of the used units to all the units.
+    pub fill_ratio: f64,
+    /// Number of keys of each length, i.e. `depth_histogram[n]` keys are `n`
+    /// bytes long.
+    pub depth_histogram: Vec<usize>,
+    /// Number of nodes with each number of children, not counting leaves,
+    /// i.e. `branching_histogram[n]` nodes have `n` children.
+    pub branching_histogram: Vec<usize>,
+    /// Number of nodes of the trie of the keys per node in the array. It is
+    /// 1 for a trie and more for a DAWG, which Darts-clone builds when values
+    /// are given and in which nodes with the same suffixes are shared.
+    pub sharing_ratio: f64,
+}
+
+impl Stats {
+    /// Returns the length of the longest key.
+    pub fn max_depth(&self) -> usize {
+        self.depth_histogram.len().saturating_sub(1)
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "keys: {}", self.num_keys)?;
+        writeln!(f, "units: {}", self.num_units)?;
+        writeln!(f, "leaf units: {}", self.num_leaf_units)?;
+        writeln!(f, "internal units: {}", self.num_internal_units)?;
+        writeln!(f, "empty units: {}", self.num_empty_units)?;
+        writeln!(f, "fill ratio: {:.4}", self.fill_ratio)?;
+        writeln!(f, "depth: {}", self.max_depth())?;
+        writeln!(f, "sharing ratio: {:.4}", self.sharing_ratio)?;
+        write!(f, "keys by length:")?;
+        for (depth, &count) in self.depth_histogram.iter().enumerate() {
+            if count > 0 {
+                write!(f, " {}:{}", depth, count)?;
+            }
+        }
+        write!(f, "\nnodes by children:")?;
+        for (num_children, &count) in self.branching_histogram.iter().enumerate() {
+            if count > 0 {
+                write!(f, " {}:{}", num_children, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DoubleArrayTrie {
+    /// Walks the array of units and returns a report of its structure.
+    pub fn stats(&self) -> Stats {
+        let units = self.array();
+        let mut stats = Stats {
+            num_keys: 0,
+            num_units: units.len(),
+            num_leaf_units: 0,
+            num_internal_units: 0,
+            num_empty_units: units.len(),
+            fill_ratio: 0.0,
+            depth_histogram: Vec::new(),
+            branching_histogram: Vec::new(),
+            sharing_ratio: 0.0,
+        };
+        if units.is_empty() {
+            return stats;
+        }
+
+        // Keys come in order, so each one adds the nodes after its common
+        // prefix with the previous one to the trie, which has a root.
+        let mut num_trie_nodes = 1;
+        let mut prev_key = Vec::new();
+        for (key, _) in self.iter() {
+            let common = key
+                .iter()
+                .zip(&prev_key)
+                .take_while(|(a, b)| a == b)
+                .count();
+            num_trie_nodes += key.len() - common;
+            if stats.depth_histogram.len() <= key.len() {
+                stats.depth_histogram.resize(key.len() + 1, 0);
+            }
+            stats.depth_histogram[key.len()] += 1;
+            stats.num_keys += 1;
+            prev_key = key;
+        }
+
+        // Shared nodes are reached several times but counted once.
+        let mut visited = vec![false; units.len()];
+        let mut stack = vec![0];
+        visited[0] = true;
+        while let Some(id) = stack.pop() {
+            stats.num_internal_units += 1;
+            if unit::has_leaf(units[id]) {
+                let leaf = id ^ unit::offset(units[id]) as usize;
+                if leaf < units.len() && !visited[leaf] {
+                    visited[leaf] = true;
+                    stats.num_leaf_units += 1;
+                }
+            }
+            let mut num_children = 0;
+            for (_, child) in unit::children(units, id) {
+                num_children += 1;
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push(child);
+                }
+            }
+            if stats.branching_histogram.len() <= num_children {
+                stats.branching_histogram.resize(num_children + 1, 0);
+            }
+            stats.branching_histogram[num_children] += 1;
+        }
+
+        let num_used = stats.num_leaf_units + stats.num_internal_units;
+        stats.num_empty_units = units.len() - num_used;
+        stats.fill_ratio = num_used as f64 / units.len() as f64;
+        stats.sharing_ratio = num_trie_nodes as f64 / stats.num_internal_units as f64;
+        stats
+    }
+}
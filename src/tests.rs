@@ -1,9 +1,24 @@
-use darts::{DoubleArrayTrie, ResultPairType};
+use archive::{Archive, ArchiveWriter, SectionKind};
+use builder::{BuildReport, DuplicatePolicy, SortingBuilder, TrieBuilder};
+use compress::Codec;
+use darts::{DoubleArrayTrie, Ownership, ResultPairType, MAX_VALUE};
+use delta::DeltaTrie;
+use dynamic::DynamicTrie;
+use error::Error;
+use external::ExternalBuilder;
+use header::{ByteOrder, Header, FLAG_INDEX_VALUES, HEADER_SIZE, VERSION};
+use merge::MergePolicy;
+use overlay::{Overlay, OverlayHit};
+use parallel::ParallelBuilder;
+use perfect_hash::PerfectHash;
+use set::TrieSet;
+use shared::SharedTrie;
+use text::TextFormat;
 use once_cell::sync::Lazy;
 use rand::{distributions::Alphanumeric, Rng};
 
 use super::*;
-use std::{collections::BTreeSet, sync::Mutex};
+use std::{collections::BTreeSet, env, path::PathBuf, process, sync::Mutex};
 
 const NUM_VALID_KEYS: usize = 1 << 16;
 const NUM_INVALID_KEYS: usize = 1 << 17;
@@ -78,7 +93,11 @@ fn generate_invalid_keys(num_keys: usize, valid_keys: &BTreeSet<String>) -> BTre
     invalid_keys
 }
 
-fn test_dic(dic: &DoubleArrayTrie, keys: &Vec<String>, lengths: &Vec<usize>, values: &Vec<i32>, invalid_keys: &BTreeSet<String>) {
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("test-darts-{}-{}.dic", process::id(), name))
+}
+
+fn test_dic(dic: &DoubleArrayTrie, keys: &[String], lengths: &[usize], values: &[i32], invalid_keys: &BTreeSet<String>) {
     let mut value: i32;
     let mut result: ResultPairType;
 
@@ -115,68 +134,68 @@ fn test_dic(dic: &DoubleArrayTrie, keys: &Vec<String>, lengths: &Vec<usize>, val
 
 #[test]
 fn build_with_keys() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, values } = &(*data);
 
-    match dic.build(keys.len(), &keys, None, None, None) {
-        Ok(_) => test_dic(&dic, &keys, &lengths, &values, &invalid_keys),
+    match dic.build(keys.len(), keys, None, None, None) {
+        Ok(_) => test_dic(&dic, keys, lengths, values, invalid_keys),
         Err(what) => panic!("{}", what),
     }
 }
 
 #[test]
 fn build_with_keys_and_lengths() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, values } = &(*data);
 
-    match dic.build(keys.len(), &keys, Some(&lengths), None, None) {
-        Ok(_) => test_dic(&dic, &keys, &lengths, &values, &invalid_keys),
+    match dic.build(keys.len(), keys, Some(lengths), None, None) {
+        Ok(_) => test_dic(&dic, keys, lengths, values, invalid_keys),
         Err(what) => panic!("{}", what),
     }
 }
 
 #[test]
 fn build_with_keys_lengths_and_values() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, values } = &(*data);
 
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&values), None) {
-        Ok(_) => test_dic(&dic, &keys, &lengths, &values, &invalid_keys),
+    match dic.build(keys.len(), keys, Some(lengths), Some(values), None) {
+        Ok(_) => test_dic(&dic, keys, lengths, values, invalid_keys),
         Err(what) => panic!("{}", what),
     }
 }
 
 #[test]
 fn build_with_keys_lengths_and_random_values() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
-        Ok(_) => test_dic(&dic, &keys, &lengths, &random, &invalid_keys),
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
+        Ok(_) => test_dic(&dic, keys, lengths, &random, invalid_keys),
         Err(what) => panic!("{}", what),
     }
 }
 
 #[test]
 fn save_and_open() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
-    let dic_copy = DoubleArrayTrie::new();
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    let mut dic_copy = DoubleArrayTrie::new();
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             assert_eq!(Ok(()), dic.save("test-darts.dic", "wb", 0));
             assert_eq!(Ok(()), dic_copy.open("test-darts.dic", "rb", 0, 0));
             assert_eq!(dic.size(), dic_copy.size());
     
-            test_dic(&dic_copy, &keys, &lengths, &random, &invalid_keys);
+            test_dic(&dic_copy, keys, lengths, &random, invalid_keys);
         },
         Err(what) => panic!("{}", what),
     }
@@ -184,20 +203,21 @@ fn save_and_open() {
 
 #[test]
 fn set_array_with_array() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
     let mut dic_copy = DoubleArrayTrie::new();
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             dic.save("test-darts.dic", "wb", 0).unwrap();
             dic_copy.open("test-darts.dic", "rb", 0, 0).unwrap();
-            dic_copy.set_array(&dic.array(), 0);
-            assert_eq!(dic_copy.size(), 0);
+            let dic_ref = DoubleArrayTrie::from_slice(dic_copy.array()).unwrap();
+            assert_eq!(dic_ref.size(), dic.size());
+            assert_eq!(dic_ref.array().as_ptr(), dic_copy.array().as_ptr());
     
-            test_dic(&dic_copy, &keys, &lengths, &random, &invalid_keys);
+            test_dic(&dic_ref, keys, lengths, &random, invalid_keys);
         },
         Err(what) => panic!("{}", what),
     }
@@ -205,20 +225,22 @@ fn set_array_with_array() {
 
 #[test]
 fn set_array_with_array_and_size() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
     let mut dic_copy = DoubleArrayTrie::new();
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             dic.save("test-darts.dic", "wb", 0).unwrap();
             dic_copy.open("test-darts.dic", "rb", 0, 0).unwrap();
-            dic_copy.set_array(&dic.array(), dic.size());
+            let units: &'static [u32] = Box::leak(dic.array().into());
+            dic_copy.set_array(units).unwrap();
             assert_eq!(dic_copy.size(), dic.size());
+            assert_eq!(dic_copy.ownership(), Ownership::Borrowed);
     
-            test_dic(&dic_copy, &keys, &lengths, &random, &invalid_keys);
+            test_dic(&dic_copy, keys, lengths, &random, invalid_keys);
         },
         Err(what) => panic!("{}", what),
     }
@@ -226,12 +248,12 @@ fn set_array_with_array_and_size() {
 
 #[test]
 fn common_prefix_search() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             for (i, key) in keys.iter().enumerate() {
                 let results = dic.common_prefix_search(key, MAX_NUM_RESULTS, 0, 0);
@@ -272,12 +294,12 @@ fn common_prefix_search() {
 
 #[test]
 fn common_longest_prefix_search() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             let mut value: i32;
             let mut result: ResultPairType;
@@ -328,12 +350,12 @@ fn common_longest_prefix_search() {
 
 #[test]
 fn tarverse() {
-    let dic = darts::DoubleArrayTrie::new();
+    let mut dic = darts::DoubleArrayTrie::new();
     let data = TEST_DATA.lock().unwrap();
     let TestData { invalid_keys, keys, lengths, .. } = &(*data);
     let random = data.random_value();
 
-    match dic.build(keys.len(), &keys, Some(&lengths), Some(&random), None) {
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
         Ok(_) => {
             for (i, key) in keys.iter().enumerate() {
                 let mut id = 0usize;
@@ -363,3 +385,1123 @@ fn tarverse() {
     }
 }
 
+#[test]
+fn iter() {
+    let mut dic = darts::DoubleArrayTrie::new();
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { keys, lengths, .. } = &(*data);
+    let random = data.random_value();
+
+    match dic.build(keys.len(), keys, Some(lengths), Some(&random), None) {
+        Ok(_) => {
+            let pairs = dic.iter().collect::<Vec<_>>();
+            assert_eq!(pairs.len(), keys.len());
+            for (i, (key, value)) in pairs.into_iter().enumerate() {
+                assert_eq!(key, keys[i].as_bytes());
+                assert_eq!(value, random[i]);
+            }
+        },
+        Err(what) => panic!("{}", what),
+    }
+}
+
+#[test]
+fn trie_set() {
+    let set: TrieSet = ["the", "a", "an", "then", "a"].into_iter().collect();
+    assert_eq!(set.len(), 4);
+    assert!(set.contains("an"));
+    assert!(set.contains("then"));
+    assert!(!set.contains("th"));
+    assert!(!set.contains(""));
+    assert!(set.contains_prefix_of("theory"));
+    assert!(set.contains_prefix_of("and"));
+    assert!(!set.contains_prefix_of("xyz"));
+    assert!(!set.contains("an\0"));
+    assert!(set.contains_prefix_of("an\0x"));
+    assert!(!set.contains_prefix_of("\0an"));
+    assert_eq!(set.iter().collect::<Vec<_>>(), ["a", "an", "the", "then"]);
+    assert!(matches!(TrieSet::build(["a", "b\0c"]), Err(Error::InvalidKey { index: 1, .. })));
+    assert!(matches!(TrieSet::build(["a", ""]), Err(Error::InvalidKey { index: 1, .. })));
+
+    let empty = TrieSet::new();
+    assert!(!empty.contains("a"));
+    assert_eq!(empty.iter().count(), 0);
+}
+
+#[test]
+fn trie_set_operations() {
+    let a: TrieSet = ["apple", "banana", "cherry"].into_iter().collect();
+    let b: TrieSet = ["banana", "cherry", "durian"].into_iter().collect();
+
+    assert_eq!(a.union(&b).iter().collect::<Vec<_>>(), ["apple", "banana", "cherry", "durian"]);
+    assert_eq!(a.intersection(&b).iter().collect::<Vec<_>>(), ["banana", "cherry"]);
+    assert_eq!(a.difference(&b).iter().collect::<Vec<_>>(), ["apple"]);
+    assert!(a.difference(&a).is_empty());
+}
+
+#[test]
+fn sorting_builder() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, .. } = &(*data);
+    let random = data.random_value();
+
+    let mut builder = SortingBuilder::new();
+    builder.extend(keys.iter().zip(random.iter().copied()).rev());
+    builder.extend(keys.iter().map(|key| (key, 100)));
+    let (dic, report) = builder.build().unwrap();
+    assert_eq!(report, BuildReport { num_keys: keys.len(), num_duplicates: keys.len() });
+
+    test_dic(&dic, keys, lengths, &random, invalid_keys);
+}
+
+#[test]
+fn sorting_builder_duplicate_policies() {
+    let pairs = [("b", 1), ("a", 2), ("b", 3), ("b", 4)];
+
+    let mut builder = SortingBuilder::new().duplicate_policy(DuplicatePolicy::KeepFirst);
+    builder.extend(pairs);
+    let (dic, report) = builder.build().unwrap();
+    assert_eq!(dic.exact_match_search("a", 0, 0), 2);
+    assert_eq!(dic.exact_match_search("b", 0, 0), 1);
+    assert_eq!(report, BuildReport { num_keys: 2, num_duplicates: 2 });
+
+    let mut builder = SortingBuilder::new().duplicate_policy(DuplicatePolicy::KeepLast);
+    builder.extend(pairs);
+    let (dic, _) = builder.build().unwrap();
+    assert_eq!(dic.exact_match_search("b", 0, 0), 4);
+
+    let merge = DuplicatePolicy::Merge(Box::new(|sum, value| sum + value));
+    let mut builder = SortingBuilder::new().duplicate_policy(merge);
+    builder.extend(pairs);
+    let (dic, _) = builder.build().unwrap();
+    assert_eq!(dic.exact_match_search("b", 0, 0), 8);
+
+    let mut builder = SortingBuilder::new().duplicate_policy(DuplicatePolicy::Error);
+    builder.extend(pairs);
+    match builder.build() {
+        Err(Error::DuplicateKey(key)) => assert_eq!(key, b"b"),
+        _ => panic!("duplicate key must be rejected"),
+    }
+}
+
+#[test]
+fn trie_builder() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, .. } = &(*data);
+    let random = data.random_value();
+
+    let mut builder = TrieBuilder::new();
+    match builder.extend(keys.iter().zip(random.iter().copied())) {
+        Ok(_) => {
+            assert_eq!(builder.len(), keys.len());
+            let dic = builder.finish().unwrap();
+            test_dic(&dic, keys, lengths, &random, invalid_keys);
+        },
+        Err(what) => panic!("{}", what),
+    }
+}
+
+#[test]
+fn trie_builder_rejects_invalid_pairs() {
+    let mut builder = TrieBuilder::new();
+    builder.insert("b", 0).unwrap();
+
+    assert!(matches!(builder.insert("a", 1), Err(Error::WrongKeyOrder { index: 1, .. })));
+    assert!(matches!(builder.insert("b", 1), Err(Error::DuplicateKey(_))));
+    assert!(matches!(builder.insert("c", -1), Err(Error::NegativeValue { index: 1, value: -1 })));
+    assert!(matches!(builder.insert("", 1), Err(Error::InvalidKey { index: 1, .. })));
+    assert!(matches!(builder.insert("c\0", 1), Err(Error::InvalidKey { index: 1, .. })));
+    assert_eq!(builder.len(), 1);
+
+    builder.insert("ba", 1).unwrap();
+    let dic = builder.finish().unwrap();
+    assert_eq!(dic.exact_match_search("b", 0, 0), 0);
+    assert_eq!(dic.exact_match_search("ba", 0, 0), 1);
+}
+
+#[test]
+fn external_builder() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, .. } = &(*data);
+    let random = data.random_value();
+
+    // A small limit makes the builder write many sorted runs, which are
+    // merged in several passes.
+    let mut builder = ExternalBuilder::new().memory_limit(1 << 16).max_fan_in(3);
+    for (key, value) in keys.iter().zip(random.iter().copied()).rev() {
+        builder.push(key, value).unwrap();
+    }
+    for key in keys.iter().step_by(3) {
+        builder.push(key, 100).unwrap();
+    }
+    assert!(matches!(builder.push("a\0", 1), Err(Error::InvalidKey { .. })));
+
+    let path = temp_path("external");
+    let report = builder.finish(&path).unwrap();
+    assert_eq!(report, BuildReport { num_keys: keys.len(), num_duplicates: keys.len().div_ceil(3) });
+
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(Ok(()), dic.open(path.to_str().unwrap(), "rb", 0, 0));
+    test_dic(&dic, keys, lengths, &random, invalid_keys);
+    // The units are little-endian whatever the host is.
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(Ok(()), dic.open_in(path.to_str().unwrap(), 0, 0, ByteOrder::Little));
+    std::fs::remove_file(&path).unwrap();
+    test_dic(&dic, keys, lengths, &random, invalid_keys);
+}
+
+#[test]
+fn external_builder_with_sorted_pairs() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let builder = ExternalBuilder::new().memory_limit(1 << 12);
+    let path = temp_path("external-sorted");
+    let report = builder.build_sorted(keys.iter().zip(values.iter().copied()), &path).unwrap();
+    assert_eq!(report, BuildReport { num_keys: keys.len(), num_duplicates: 0 });
+
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(Ok(()), dic.open(path.to_str().unwrap(), "rb", 0, 0));
+    test_dic(&dic, keys, lengths, values, invalid_keys);
+
+    let pairs = [("b", 0), ("a", 1)];
+    assert!(matches!(builder.build_sorted(pairs, &path), Err(Error::WrongKeyOrder { index: 1, .. })));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn parallel_builder() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, .. } = &(*data);
+    let random = data.random_value();
+
+    let dic = ParallelBuilder::new().num_threads(4).build(keys, Some(&random)).unwrap();
+    test_dic(&dic, keys, lengths, &random, invalid_keys);
+
+    let mut sequential = DoubleArrayTrie::new();
+    sequential.build(keys.len(), keys, None, Some(&random), None).unwrap();
+    assert!(dic.iter().eq(sequential.iter()));
+
+    // The layout does not depend on the number of threads.
+    let single = ParallelBuilder::new().num_threads(1).build(keys, Some(&random)).unwrap();
+    assert_eq!(dic.array(), single.array());
+}
+
+#[test]
+fn parallel_builder_rejects_invalid_keys() {
+    let builder = ParallelBuilder::new();
+    assert!(matches!(builder.build(&["b", "a"], None), Err(Error::WrongKeyOrder { index: 1, .. })));
+    assert!(matches!(builder.build(&["ab", "aa"], None), Err(Error::WrongKeyOrder { index: 1, .. })));
+    assert!(matches!(builder.build(&["a", ""], None), Err(Error::InvalidKey { index: 1, .. })));
+    assert!(matches!(builder.build(&["a", "b"], Some(&[0, -1])), Err(Error::NegativeValue { index: 1, .. })));
+    assert!(matches!(
+        builder.build(&["a", "b"], Some(&[0])),
+        Err(Error::CountMismatch { what: "values", count: 1, num_keys: 2 })
+    ));
+
+    let dic = builder.build(&["a", "a", "b"], None).unwrap();
+    assert_eq!(dic.exact_match_search("a", 0, 0), 0);
+    assert_eq!(dic.exact_match_search("b", 0, 0), 2);
+    assert_eq!(builder.build::<&str>(&[], None).unwrap().exact_match_search("a", 0, 0), -1);
+}
+
+#[test]
+fn build_from_reader() {
+    let mut dic = DoubleArrayTrie::new();
+    let input = "# fruits\r\nbanana\t2\n\napple\t1\nba\\tna\\\\na\t3\nbanana\t4\n";
+    let format = TextFormat::tsv().comment("#");
+    let report = dic.build_from_reader(input.as_bytes(), &format).unwrap();
+    assert_eq!(report, BuildReport { num_keys: 3, num_duplicates: 1 });
+    assert_eq!(dic.exact_match_search("banana", 0, 0), 2);
+    assert_eq!(dic.exact_match_search("ba\tna\\na", 0, 0), 3);
+
+    let input = "word,count\n\"a,b\",5\n\"say \"\"hi\"\"\nthere\",6\nc,7\n";
+    let format = TextFormat::csv(0, Some(1)).header(true);
+    dic.build_from_reader(input.as_bytes(), &format).unwrap();
+    let keys = dic.iter().collect::<Vec<_>>();
+    assert_eq!(keys, [(b"a,b".to_vec(), 5), (b"c".to_vec(), 7), (b"say \"hi\"\nthere".to_vec(), 6)]);
+
+    let report = dic.build_from_reader("c\nb\n\na\n".as_bytes(), &TextFormat::lines()).unwrap();
+    assert_eq!(report.num_keys, 3);
+    assert_eq!(dic.exact_match_search("c", 0, 0), 2);
+
+    dic.build_from_reader(&b"\xff\xfe\t1\n"[..], &TextFormat::tsv()).unwrap();
+    assert_eq!(dic.exact_match_search_bytes(b"\xff\xfe", 0, 0), 1);
+    assert_eq!(dic.exact_match_search_bytes(b"\xff\0", 0, 0), -1);
+}
+
+#[test]
+fn build_from_reader_reports_lines() {
+    let mut dic = DoubleArrayTrie::new();
+    let errors = [
+        ("a\t1\n\nb\t-2\n", TextFormat::tsv(), 3),
+        ("a\t1\nb\tx\n", TextFormat::tsv(), 2),
+        ("a\t1\nb\n", TextFormat::tsv(), 2),
+        ("a\\x\t1\n", TextFormat::tsv(), 1),
+        ("a,1\n\"b\nc\",-2\n", TextFormat::csv(0, Some(1)), 2),
+        ("a,1\nd\n", TextFormat::csv(0, Some(1)), 2),
+        ("a,1\n\"b,2\n", TextFormat::csv(0, Some(1)), 2),
+    ];
+    for (input, format, line) in errors {
+        match dic.build_from_reader(input.as_bytes(), &format) {
+            Err(Error::Parse { line: actual, .. }) => assert_eq!(actual, line, "{:?}", input),
+            _ => panic!("{:?} must be rejected", input),
+        }
+    }
+}
+
+#[test]
+fn save_and_open_with_header() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let path = temp_path("header");
+    let file_name = path.to_str().unwrap();
+    let header = dic.save_with_header(file_name, FLAG_INDEX_VALUES).unwrap();
+    assert_eq!(header.num_units, dic.size() as u64);
+    assert_eq!(header.num_keys, keys.len() as u64);
+
+    let mut dic_copy = DoubleArrayTrie::new();
+    assert_eq!(dic_copy.open_with_header(file_name, 0).unwrap(), header);
+    test_dic(&dic_copy, keys, lengths, values, invalid_keys);
+    assert_eq!(dic_copy.open_any(file_name, 0).unwrap(), Some(header));
+
+    // Corrupting a unit or truncating the array is detected.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[HEADER_SIZE + 100] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(dic_copy.open_with_header(file_name, 0), Err(Error::ChecksumMismatch { .. })));
+    bytes.truncate(bytes.len() - 4);
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(dic_copy.open_with_header(file_name, 0), Err(Error::InvalidFormat(_))));
+
+    // A root pointing out of the array is caught even with a valid checksum.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.extend_from_slice(&[0; 4]);
+    bytes[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&(0x3FFF_FC00u32).to_le_bytes());
+    let checksum = crc32fast::hash(&bytes[HEADER_SIZE..]);
+    bytes[32..36].copy_from_slice(&checksum.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(dic_copy.open_with_header(file_name, 0), Err(Error::InvalidFormat(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_raw_file_with_header() {
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(2, &["a".to_owned(), "b".to_owned()], None, None, None).unwrap();
+    let path = temp_path("raw");
+    let file_name = path.to_str().unwrap();
+    assert_eq!(Ok(()), dic.save(file_name, "wb", 0));
+
+    let mut dic_copy = DoubleArrayTrie::new();
+    assert!(matches!(dic_copy.open_with_header(file_name, 0), Err(Error::InvalidFormat(_))));
+    assert_eq!(dic_copy.open_any(file_name, 0).unwrap(), None);
+    assert_eq!(dic_copy.exact_match_search("b", 0, 0), 1);
+
+    let mut header = Header::from_bytes(&dic.header(0).to_bytes()).unwrap();
+    for version in [VERSION - 1, VERSION + 1] {
+        header.version = version;
+        assert!(matches!(Header::from_bytes(&header.to_bytes()), Err(Error::InvalidFormat(_))));
+    }
+    header.version = VERSION;
+    for num_units in [0, u64::MAX / 2] {
+        header.num_units = num_units;
+        assert!(matches!(Header::from_bytes(&header.to_bytes()), Err(Error::InvalidFormat(_))));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn archive() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut words = DoubleArrayTrie::new();
+    words.build(keys.len(), keys, None, None, None).unwrap();
+    let mut fruits = TrieBuilder::new();
+    fruits.extend([("apple", 10), ("banana", 20)]).unwrap();
+    let fruits = fruits.finish().unwrap();
+
+    let path = temp_path("archive");
+    let mut writer = ArchiveWriter::new();
+    writer.add_dictionary("words", &words, FLAG_INDEX_VALUES);
+    writer.add_table("labels", b"red\0yellow");
+    writer.add_dictionary("fruits", &fruits, 0);
+    let sections = writer.write(&path).unwrap();
+    assert_eq!(sections.len(), 3);
+    assert!(sections.iter().all(|section| section.offset % 8 == 0));
+
+    for (archive, ownership) in [
+        (Archive::open(&path).unwrap(), Ownership::Owned),
+        (Archive::map(&path).unwrap(), Ownership::Mapped),
+    ] {
+        assert_eq!(archive.sections(), &sections[..]);
+        assert_eq!(archive.dictionary("words").unwrap().ownership(), ownership);
+        assert_eq!(archive.section("labels").unwrap().kind, SectionKind::Table);
+        test_dic(&archive.dictionary("words").unwrap(), keys, lengths, values, invalid_keys);
+        assert_eq!(archive.dictionary("fruits").unwrap().exact_match_search("banana", 0, 0), 20);
+        assert_eq!(&archive.table("labels").unwrap()[..], b"red\0yellow");
+        assert!(matches!(archive.dictionary("labels"), Err(Error::InvalidFormat(_))));
+        assert!(matches!(archive.table("colors"), Err(Error::SectionNotFound(_))));
+    }
+
+    // A dictionary section can be opened on its own.
+    let mut dic = DoubleArrayTrie::new();
+    let offset = sections[2].offset as usize;
+    dic.open_with_header(path.to_str().unwrap(), offset).unwrap();
+    assert_eq!(dic.exact_match_search("apple", 0, 0), 10);
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[sections[1].offset as usize] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+    let archive = Archive::map(&path).unwrap();
+    assert!(matches!(archive.table("labels"), Err(Error::ChecksumMismatch { .. })));
+    assert!(archive.verify(false).table("labels").is_ok());
+    // The flags of the header are not covered by the checksum of the array.
+    bytes[sections[2].offset as usize + 12] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+    for archive in [Archive::open(&path).unwrap(), Archive::map(&path).unwrap()] {
+        assert!(matches!(archive.dictionary("fruits"), Err(Error::ChecksumMismatch { .. })));
+    }
+
+    // Sections which are not aligned or shorter than their array are
+    // rejected, whatever the checksums say.
+    let bytes = std::fs::read(&path).unwrap();
+    let mut unaligned = bytes.clone();
+    unaligned[20] += 4;
+    std::fs::write(&path, &unaligned).unwrap();
+    assert!(matches!(Archive::open(&path), Err(Error::InvalidFormat(_))));
+    assert!(matches!(Archive::map(&path), Err(Error::InvalidFormat(_))));
+    let mut short = bytes.clone();
+    short[28..36].copy_from_slice(&(HEADER_SIZE as u64 + 4).to_le_bytes());
+    let toc_end = 16 + sections.iter().map(|section| 24 + section.name.len()).sum::<usize>();
+    let checksum = crc32fast::hash(&short[16..toc_end]);
+    short[toc_end..toc_end + 4].copy_from_slice(&checksum.to_le_bytes());
+    std::fs::write(&path, &short).unwrap();
+    for archive in [Archive::open(&path).unwrap(), Archive::map(&path).unwrap()] {
+        assert!(matches!(archive.verify(false).dictionary("words"), Err(Error::InvalidFormat(_))));
+    }
+
+    writer.add_table("labels", b"");
+    assert!(matches!(writer.write(&path), Err(Error::InvalidFormat(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn byte_order() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let path = temp_path("byte-order");
+    let file_name = path.to_str().unwrap();
+
+    // Writing in the other byte order simulates a file from a host of the
+    // other endianness.
+    let other = match ByteOrder::NATIVE {
+        ByteOrder::Little => ByteOrder::Big,
+        ByteOrder::Big => ByteOrder::Little,
+    };
+    let mut out = std::fs::File::create(&path).unwrap();
+    dic.write_with_header_in(&mut out, 0, other).unwrap();
+    drop(out);
+    let mut dic_copy = DoubleArrayTrie::new();
+    assert_eq!(dic_copy.open_with_header(file_name, 0).unwrap().byte_order, other);
+    test_dic(&dic_copy, keys, lengths, values, invalid_keys);
+    assert_eq!(dic.save_with_header(file_name, 0).unwrap().byte_order, ByteOrder::Little);
+
+    assert_eq!(Ok(()), dic.save_in(file_name, "wb", 0, other));
+    let mut dic_copy = DoubleArrayTrie::new();
+    assert_eq!(Ok(()), dic_copy.open_in(file_name, 0, 0, other));
+    assert_eq!(dic_copy.array(), dic.array());
+
+    // An unknown byte order mark is rejected, whatever the version.
+    let mut bytes = dic.header(0).to_bytes();
+    bytes[36] = 0;
+    assert!(matches!(Header::from_bytes(&bytes), Err(Error::InvalidFormat(_))));
+    bytes[8..10].copy_from_slice(&1u16.to_le_bytes());
+    assert!(matches!(Header::from_bytes(&bytes), Err(Error::InvalidFormat(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Codec storing data as is, to test the pluggable codecs.
+struct StoredCodec;
+
+impl Codec for StoredCodec {
+    fn id(&self) -> u8 {
+        200
+    }
+
+    fn compress(&self, data: &[u8], out: &mut dyn std::io::Write) -> std::io::Result<()> {
+        out.write_all(data)
+    }
+
+    fn decompress(&self, input: &mut dyn std::io::Read, out: &mut Vec<u8>) -> std::io::Result<()> {
+        input.read_to_end(out).map(|_| ())
+    }
+}
+
+#[test]
+fn compressed_with_custom_codec() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let path = temp_path("compressed-custom");
+    let file_name = path.to_str().unwrap();
+
+    let header = dic.save_compressed(file_name, &StoredCodec, FLAG_INDEX_VALUES).unwrap();
+    assert_eq!(header.num_keys, keys.len() as u64);
+    let mut dic_copy = DoubleArrayTrie::new();
+    assert_eq!(dic_copy.open_compressed_with(file_name, &StoredCodec).unwrap(), header);
+    test_dic(&dic_copy, keys, lengths, values, invalid_keys);
+
+    // The codec is not built in, and a corrupted array is detected after
+    // decompression.
+    assert!(matches!(dic_copy.open_compressed(file_name), Err(Error::InvalidFormat(_))));
+    assert!(matches!(dic_copy.open_any(file_name, 0), Err(Error::InvalidFormat(_))));
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    let result = dic_copy.read_compressed_with(&mut bytes.as_slice(), &StoredCodec);
+    assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+#[test]
+fn compressed_with_builtin_codecs() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let path = temp_path("compressed-builtin");
+    let file_name = path.to_str().unwrap();
+
+    let codecs: Vec<Box<dyn Codec>> = vec![
+        #[cfg(feature = "zstd")]
+        Box::new(compress::Zstd::default()),
+        #[cfg(feature = "lz4")]
+        Box::new(compress::Lz4),
+    ];
+    for codec in codecs {
+        let header = dic.save_compressed(file_name, codec.as_ref(), 0).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < header.payload_size());
+        let mut dic_copy = DoubleArrayTrie::new();
+        assert_eq!(dic_copy.open_compressed(file_name).unwrap(), header);
+        test_dic(&dic_copy, keys, lengths, values, invalid_keys);
+        let mut dic_copy = DoubleArrayTrie::new();
+        assert_eq!(dic_copy.open_any(file_name, 0).unwrap(), Some(header));
+        assert_eq!(dic_copy.array(), dic.array());
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Bundle {
+        name: String,
+        dic: DoubleArrayTrie,
+    }
+
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let bundle = Bundle { name: "words".to_owned(), dic };
+
+    let bytes = bincode::serialize(&bundle).unwrap();
+    let copy: Bundle = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(copy.name, "words");
+    test_dic(&copy.dic, keys, lengths, values, invalid_keys);
+
+    // The units are stored as one block of bytes.
+    let bytes = postcard::to_allocvec(&bundle).unwrap();
+    assert!(bytes.len() < bundle.dic.total_size() + 16);
+    let copy: Bundle = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(copy.dic.array(), bundle.dic.array());
+
+    let bytes = rmp_serde::to_vec(&bundle).unwrap();
+    let copy: Bundle = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(copy.dic.array(), bundle.dic.array());
+
+    // An empty dictionary has no units, and a truncated array is rejected.
+    let bytes = bincode::serialize(&DoubleArrayTrie::new()).unwrap();
+    let empty: DoubleArrayTrie = bincode::deserialize(&bytes).unwrap();
+    assert!(empty.array().is_empty());
+    let bytes = bincode::serialize(&[0u8; 1000].as_slice()).unwrap();
+    assert!(bincode::deserialize::<DoubleArrayTrie>(&bytes).is_err());
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn rkyv_archive() {
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    struct Bundle {
+        dic: DoubleArrayTrie,
+        labels: Vec<String>,
+    }
+
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let labels = keys.iter().map(|key| key.to_uppercase()).collect();
+    let bundle = Bundle { dic, labels };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&bundle).unwrap();
+
+    // The archived units are searched where they are.
+    let archived = rkyv::access::<ArchivedBundle, rkyv::rancor::Error>(&bytes).unwrap();
+    let dic = archived.dic.as_trie();
+    let units = archived.dic.units().as_ptr() as usize - bytes.as_ptr() as usize;
+    if ByteOrder::NATIVE == ByteOrder::Little {
+        assert_eq!(dic.array().as_ptr() as *const u8, archived.dic.units().as_ptr() as *const u8);
+    }
+    test_dic(&dic, keys, lengths, values, invalid_keys);
+    let key = &keys[keys.len() / 2];
+    let value = dic.exact_match_search(key, key.len(), 0);
+    assert_eq!(archived.labels[value as usize], key.to_uppercase());
+
+    let copy = rkyv::deserialize::<Bundle, rkyv::rancor::Error>(archived).unwrap();
+    assert_eq!(copy.dic.array(), bundle.dic.array());
+    assert_eq!(copy.labels, bundle.labels);
+
+    // Corrupted archives are rejected.
+    let mut bytes = bytes.to_vec();
+    let len = bytes.len();
+    bytes[len - 4..].fill(0xFF);
+    assert!(rkyv::access::<ArchivedBundle, rkyv::rancor::Error>(&bytes).is_err());
+    // So are units referring to children out of the array.
+    let mut bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&bundle).unwrap();
+    bytes[units..units + 4].copy_from_slice(&0x3FFF_FC00u32.to_le_bytes());
+    assert!(rkyv::access::<ArchivedBundle, rkyv::rancor::Error>(&bytes).is_err());
+}
+
+#[test]
+fn shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DoubleArrayTrie>();
+
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { keys, values, .. } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, Some(values), None).unwrap();
+    let dic = std::sync::Arc::new(dic);
+    let pairs = std::sync::Arc::new(keys.iter().cloned().zip(values.iter().copied()).collect::<Vec<_>>());
+
+    let threads = (0..8)
+        .map(|thread| {
+            let dic = dic.clone();
+            let pairs = pairs.clone();
+            std::thread::spawn(move || {
+                for (key, value) in pairs.iter().skip(thread).step_by(3) {
+                    assert_eq!(dic.exact_match_search(key, key.len(), 0), *value);
+                    let results = dic.common_prefix_search(key, MAX_NUM_RESULTS, key.len(), 0);
+                    assert_eq!(results.last().unwrap().value, *value);
+                    assert_eq!(dic.common_longest_prefix_search(key, key.len(), 0), *value);
+                }
+                assert_eq!(dic.iter().count(), pairs.len());
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Each thread building at the same time gets its own progress callback.
+    let threads = (0..4)
+        .map(|_| {
+            let pairs = pairs.clone();
+            std::thread::spawn(move || {
+                let keys = pairs.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+                let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                let counter = calls.clone();
+                let progress = move |current: usize, total: usize| {
+                    assert!(current <= total);
+                    counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    0
+                };
+                let mut dic = DoubleArrayTrie::new();
+                dic.build(keys.len(), &keys, None, None, Some(Box::new(progress))).unwrap();
+                assert!(calls.load(std::sync::atomic::Ordering::Relaxed) > 0);
+                dic.array().to_vec()
+            })
+        })
+        .collect::<Vec<_>>();
+    let units = threads.into_iter().map(|thread| thread.join().unwrap()).collect::<Vec<_>>();
+    assert!(units.windows(2).all(|pair| pair[0] == pair[1]));
+}
+
+#[test]
+fn shared_trie() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { keys, .. } = &(*data);
+    let (old_keys, new_keys) = keys.split_at(keys.len() / 2);
+    let build = |keys: &[String]| {
+        let mut dic = DoubleArrayTrie::new();
+        dic.build(keys.len(), keys, None, None, None).unwrap();
+        dic
+    };
+
+    let shared = std::sync::Arc::new(SharedTrie::new(build(old_keys)));
+    let old = shared.load();
+    shared.store(build(new_keys));
+    // A dictionary loaded before the swap is still searchable.
+    assert_eq!(old.exact_match_search(&old_keys[1], old_keys[1].len(), 0), 1);
+    assert_eq!(shared.load().exact_match_search(&new_keys[1], new_keys[1].len(), 0), 1);
+    drop(old);
+
+    let path = temp_path("shared");
+    let file_name = path.to_str().unwrap();
+    build(old_keys).save_with_header(file_name, 0).unwrap();
+    assert!(shared.reload(file_name).unwrap().is_some());
+    assert_eq!(shared.load().iter().count(), old_keys.len());
+    assert!(shared.reload(temp_path("missing").to_str().unwrap()).is_err());
+    assert_eq!(shared.load().iter().count(), old_keys.len());
+    // A raw array with a unit of its second block referring to units out of
+    // the array, which darts-clone does not check.
+    let mut garbage = vec![0u8; 512 * 4];
+    garbage[..4].copy_from_slice(&ByteOrder::Little.encode(1 << 10));
+    garbage[300 * 4..301 * 4].copy_from_slice(&ByteOrder::Little.encode(0x1000 << 10));
+    std::fs::write(&path, &garbage).unwrap();
+    assert!(matches!(shared.reload(file_name), Err(Error::Darts(_))));
+    assert_eq!(shared.load().iter().count(), old_keys.len());
+
+    // Only dictionaries holding the first key of the new keys are accepted.
+    let probe = new_keys[0].clone();
+    let watcher = shared.watch(&path, std::time::Duration::from_millis(10), move |path| {
+        let mut dic = DoubleArrayTrie::new();
+        dic.open_with_header(path.to_str().unwrap(), 0)?;
+        if dic.exact_match_search(&probe, probe.len(), 0) < 0 {
+            return Err(Error::InvalidFormat("missing key".to_owned()));
+        }
+        Ok(dic)
+    });
+    let wait = |done: &dyn Fn() -> bool| {
+        for _ in 0..500 {
+            if done() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("the watcher did not react");
+    };
+    build(&keys[..keys.len() / 4]).save_with_header(file_name, 0).unwrap();
+    wait(&|| matches!(watcher.take_error(), Some(Error::InvalidFormat(_))));
+    assert_eq!(shared.load().iter().count(), old_keys.len());
+    build(new_keys).save_with_header(file_name, 0).unwrap();
+    wait(&|| watcher.num_reloads() == 1);
+    assert_eq!(shared.load().iter().count(), new_keys.len());
+    drop(watcher);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn stats() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { keys, .. } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(dic.stats().num_keys, 0);
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let stats = dic.stats();
+    assert_eq!(stats.num_keys, keys.len());
+    assert_eq!(stats.num_units, dic.size());
+    assert_eq!(stats.num_leaf_units, keys.len());
+    assert_eq!(stats.num_leaf_units + stats.num_internal_units + stats.num_empty_units, stats.num_units);
+    assert!(stats.fill_ratio > 0.5 && stats.fill_ratio <= 1.0);
+    assert_eq!(stats.depth_histogram.iter().sum::<usize>(), keys.len());
+    assert_eq!(stats.max_depth(), keys.iter().map(String::len).max().unwrap());
+    assert_eq!(stats.branching_histogram.iter().sum::<usize>(), stats.num_internal_units);
+    // A trie has a node per distinct prefix.
+    assert_eq!(stats.sharing_ratio, 1.0);
+
+    // A DAWG of "ac" and "bc" whose nodes for "c" are one node.
+    let mut units = vec![0; 256];
+    units[0] = layout::node_unit(0, false, 0x80).unwrap();
+    units[0xE1] = layout::node_unit(b'a', false, 0xE1 ^ 0x63 ^ 0x10).unwrap();
+    units[0xE2] = layout::node_unit(b'b', false, 0xE2 ^ 0x63 ^ 0x10).unwrap();
+    units[0x10] = layout::node_unit(b'c', true, 0x30).unwrap();
+    units[0x20] = layout::value_unit(7);
+    let dic = DoubleArrayTrie::from_units(units).unwrap();
+    assert_eq!(dic.iter().collect::<Vec<_>>(), vec![(b"ac".to_vec(), 7), (b"bc".to_vec(), 7)]);
+    let stats = dic.stats();
+    assert_eq!(stats.num_keys, 2);
+    assert_eq!(stats.num_leaf_units, 1);
+    assert_eq!(stats.num_internal_units, 4);
+    assert_eq!(stats.depth_histogram, vec![0, 0, 2]);
+    assert_eq!(stats.branching_histogram, vec![1, 2, 1]);
+    assert_eq!(stats.sharing_ratio, 1.25);
+    assert!(stats.to_string().starts_with("keys: 2\n"));
+}
+
+#[test]
+fn to_dot() {
+    let keys = ["a\"b", "ab", "abc", "x\u{1}", "日本"].map(str::to_owned);
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), &keys, None, None, None).unwrap();
+
+    let dot = dic.to_dot();
+    assert!(dot.starts_with("digraph trie {\n") && dot.ends_with("}\n"));
+    assert_eq!(dot.matches("shape=doublecircle").count(), keys.len());
+    assert_eq!(dot.matches(" -> ").count(), 9);
+    for label in ["a", "\\\"", "b", "c", "x", "\\\\x01", "日", "本"] {
+        assert!(dot.contains(&format!("[label=\"{}\"];", label)), "{}", label);
+    }
+    let mut node_pos = 0;
+    dic.traverse("ab", &mut node_pos, &mut 0, 2);
+    assert!(dot.contains(&format!("n{} [label=\"{}: 1\", shape=doublecircle];", node_pos, node_pos)));
+
+    // Only the transitions from the root are drawn, and their targets are
+    // cut off.
+    let dot = dic.to_dot_from(0, Some(1));
+    assert_eq!(dot.matches(" -> ").count(), 3);
+    assert_eq!(dot.matches("style=dashed").count(), 3);
+    let dot = dic.to_dot_from(node_pos, None);
+    assert_eq!(dot.matches(" -> ").count(), 1);
+    assert_eq!(dic.to_dot_from(usize::MAX, None), "digraph trie {\n    node [shape=circle];\n}\n");
+}
+
+#[test]
+fn clone_and_compare() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let copy = dic.clone();
+    assert_eq!(copy, dic);
+    assert_ne!(copy.array().as_ptr(), dic.array().as_ptr());
+    dic.clear();
+    test_dic(&copy, keys, lengths, values, invalid_keys);
+    assert_ne!(copy, dic);
+    assert_eq!(dic.clone(), DoubleArrayTrie::new());
+
+    // Arrays laid out differently can hold the same pairs.
+    let words = ["apple", "banana", "cherry"].map(str::to_owned);
+    let mut trie = DoubleArrayTrie::new();
+    trie.build(words.len(), &words, None, None, None).unwrap();
+    let mut parallel = ParallelBuilder::new().build(&words, None).unwrap();
+    assert_ne!(trie, parallel);
+    assert!(trie.same_contents(&parallel));
+    parallel.build(2, &words, None, None, None).unwrap();
+    assert!(!trie.same_contents(&parallel));
+
+    assert_eq!(
+        format!("{:?}", trie),
+        format!("DoubleArrayTrie {{ size: {}, total_size: {}, keys: [\"apple\", \"banana\", \"cherry\"] }}", trie.size(), trie.total_size())
+    );
+    assert!(format!("{:?}", copy).ends_with(", ..] }"));
+}
+
+#[test]
+fn ownership() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, lengths, values } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(dic.ownership(), Ownership::None);
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    assert_eq!(dic.ownership(), Ownership::Owned);
+    let units = dic.clone().into_units();
+    assert_eq!(units, dic.array());
+
+    // Moving the units in and out again does not copy them.
+    let dic = DoubleArrayTrie::from_units(units).unwrap();
+    assert_eq!(dic.ownership(), Ownership::Owned);
+    let ptr = dic.array().as_ptr();
+    let units = dic.into_units();
+    assert_eq!(units.as_ptr(), ptr);
+    let mut dic = DoubleArrayTrie::from_units(units).unwrap();
+    test_dic(&dic, keys, lengths, values, invalid_keys);
+    dic.clear();
+    assert_eq!(dic.ownership(), Ownership::None);
+    assert!(dic.array().is_empty());
+    assert!(DoubleArrayTrie::from_units(Vec::new()).unwrap().array().is_empty());
+
+    // Units which could make searches read out of the array are rejected.
+    assert!(DoubleArrayTrie::from_units(vec![0; 100]).is_err());
+    let mut units = vec![0; 256];
+    units[0] = layout::node_unit(0, false, 0x1000).unwrap();
+    assert!(DoubleArrayTrie::from_units(units.clone()).is_err());
+    assert!(DoubleArrayTrie::from_slice(&units).is_err());
+    units[0] = layout::node_unit(0, false, 0x80).unwrap();
+    assert!(DoubleArrayTrie::from_slice(&units).is_ok());
+}
+
+#[test]
+fn merge() {
+    let build = |pairs: &[(&str, i32)]| {
+        let mut builder = TrieBuilder::new();
+        builder.extend(pairs.iter().copied()).unwrap();
+        builder.finish().unwrap()
+    };
+    let system = build(&[("apple", 1), ("banana", 2), ("cherry", 3)]);
+    let user = build(&[("banana", 20), ("date", 40)]);
+
+    let (dic, report) = DoubleArrayTrie::merge(&system, &user, MergePolicy::PreferLeft).unwrap();
+    assert_eq!(report, BuildReport { num_keys: 4, num_duplicates: 1 });
+    let pairs = dic.iter().map(|(key, value)| (String::from_utf8(key).unwrap(), value)).collect::<Vec<_>>();
+    assert_eq!(pairs, [("apple".to_owned(), 1), ("banana".to_owned(), 2), ("cherry".to_owned(), 3), ("date".to_owned(), 40)]);
+
+    let (dic, _) = DoubleArrayTrie::merge(&system, &user, MergePolicy::PreferRight).unwrap();
+    assert_eq!(dic.exact_match_search("banana", 0, 0), 20);
+    let (dic, _) = DoubleArrayTrie::merge(&system, &user, MergePolicy::Combine(Box::new(|a, b| a + b))).unwrap();
+    assert_eq!(dic.exact_match_search("banana", 0, 0), 22);
+    assert_eq!(dic.exact_match_search("apple", 0, 0), 1);
+
+    let (dic, report) = DoubleArrayTrie::merge(&system, &DoubleArrayTrie::new(), MergePolicy::default()).unwrap();
+    assert!(dic.same_contents(&system));
+    assert_eq!(report.num_duplicates, 0);
+    let result = DoubleArrayTrie::merge(&system, &user, MergePolicy::Combine(Box::new(|a, b| a - b)));
+    assert!(matches!(result, Err(Error::NegativeValue { index: 1, value: -18 })));
+}
+
+#[test]
+fn overlay() {
+    let build = |pairs: &[(&str, i32)]| {
+        let mut builder = TrieBuilder::new();
+        builder.extend(pairs.iter().copied()).unwrap();
+        builder.finish().unwrap()
+    };
+    let user = build(&[("apple", 100)]);
+    let team = build(&[("app", 20), ("banana", 21)]);
+    let system = build(&[("app", 1), ("apple", 2), ("applet", 3), ("cherry", 4)]);
+    let removed_by_team: TrieSet = ["applet", "cherry"].into_iter().collect();
+    let empty = DoubleArrayTrie::new();
+    let overlay = Overlay::new()
+        .layer(&user)
+        .layer_with_tombstones(&team, &removed_by_team)
+        .layer(&empty)
+        .layer(&system);
+    assert_eq!(overlay.len(), 4);
+
+    let hit = |layer, value, length| Some(OverlayHit { layer, value, length });
+    assert_eq!(overlay.exact_match_search("apple"), hit(0, 100, 5));
+    assert_eq!(overlay.exact_match_search("app"), hit(1, 20, 3));
+    assert_eq!(overlay.exact_match_search("banana"), hit(1, 21, 6));
+    assert_eq!(overlay.exact_match_search("cherry"), None);
+    assert_eq!(overlay.exact_match_search("applet"), None);
+    assert_eq!(overlay.exact_match_search("grape"), None);
+
+    assert_eq!(
+        overlay.common_prefix_search("applets"),
+        [hit(1, 20, 3).unwrap(), hit(0, 100, 5).unwrap()]
+    );
+    assert_eq!(overlay.common_longest_prefix_search("applets"), hit(0, 100, 5));
+    assert_eq!(overlay.common_longest_prefix_search("cherry pie"), None);
+    assert_eq!(overlay.exact_match_search("apple\0"), None);
+    assert_eq!(overlay.common_longest_prefix_search("app\0le"), hit(1, 20, 3));
+
+    // Without the tombstones, the system entries are visible again.
+    let overlay = Overlay::new().layer(&user).layer(&team).layer(&system);
+    assert_eq!(overlay.exact_match_search("cherry"), hit(2, 4, 6));
+    assert_eq!(overlay.common_longest_prefix_search("applets"), hit(2, 3, 6));
+}
+
+#[test]
+fn delta_trie() {
+    let mut builder = TrieBuilder::new();
+    builder.extend([("apple", 1), ("banana", 2), ("cherry", 3)]).unwrap();
+    let mut dic = DeltaTrie::new(builder.finish().unwrap()).threshold(3);
+
+    dic.insert("app", 10).unwrap();
+    dic.insert("apple", 11).unwrap();
+    assert_eq!(dic.remove("banana"), Some(2));
+    assert_eq!(dic.remove("grape"), None);
+    assert!(matches!(dic.insert("", 0), Err(Error::InvalidKey { index: 3, .. })));
+    assert!(matches!(dic.insert("date", -1), Err(Error::NegativeValue { .. })));
+    // The third edit started a rebuild, and searches see all the edits meanwhile.
+    assert_eq!(dic.num_pending_edits(), 3);
+    dic.insert("date", 4).unwrap();
+    let check = |dic: &DeltaTrie| {
+        assert_eq!(dic.exact_match_search("apple"), Some(11));
+        assert_eq!(dic.exact_match_search("banana"), None);
+        assert_eq!(dic.exact_match_search("cherry"), Some(3));
+        assert_eq!(dic.exact_match_search("date"), Some(4));
+        assert_eq!(
+            dic.common_prefix_search("apples"),
+            [ResultPairType { value: 10, length: 3 }, ResultPairType { value: 11, length: 5 }]
+        );
+        assert_eq!(dic.common_longest_prefix_search("bananas"), None);
+        assert_eq!(dic.common_longest_prefix_search("dates"), Some(ResultPairType { value: 4, length: 4 }));
+    };
+    check(&dic);
+
+    dic.flush().unwrap();
+    assert_eq!(dic.num_pending_edits(), 0);
+    assert!(!dic.is_rebuilding());
+    assert!(dic.take_error().is_none());
+    let pairs = dic.base().iter().map(|(key, value)| (String::from_utf8(key).unwrap(), value)).collect::<Vec<_>>();
+    assert_eq!(pairs, [("app".to_owned(), 10), ("apple".to_owned(), 11), ("cherry".to_owned(), 3), ("date".to_owned(), 4)]);
+    check(&dic);
+
+    for key in ["app", "apple", "cherry", "date"] {
+        dic.remove(key).unwrap();
+    }
+    dic.flush().unwrap();
+    assert!(dic.base().array().is_empty());
+    assert_eq!(dic.exact_match_search("apple"), None);
+    assert!(dic.common_prefix_search("apple").is_empty());
+}
+
+#[test]
+fn dynamic_insert() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { keys, values, .. } = &(*data);
+    let keys = &keys[..4096];
+    let values = &values[..4096];
+
+    // Builds every other key and inserts the others in random order.
+    let (built, inserted): (Vec<_>, Vec<_>) = (0..keys.len()).partition(|i| i % 2 == 0);
+    let mut builder = TrieBuilder::new();
+    builder.extend(built.iter().map(|&i| (&keys[i], values[i]))).unwrap();
+    let mut dynamic = DynamicTrie::from_trie(builder.finish().unwrap());
+    let mut inserted = inserted;
+    inserted.sort_by_key(|&i| keys[i].bytes().rev().collect::<Vec<_>>());
+    for &i in &inserted {
+        assert_eq!(dynamic.insert(&keys[i], values[i]).unwrap(), None);
+    }
+    assert_eq!(dynamic.insert(&keys[0], 12345).unwrap(), Some(values[0]));
+    assert_eq!(dynamic.insert(&keys[0], values[0]).unwrap(), Some(12345));
+    assert!(matches!(dynamic.insert("a\0b", 1), Err(Error::InvalidKey { index: 2050, .. })));
+    assert_eq!(dynamic.exact_match_search(&keys[1]), Some(values[1]));
+
+    let dic = dynamic.into_trie();
+    assert!(DoubleArrayTrie::from_units(dic.array().to_vec()).is_ok());
+    let pairs = dic.iter().collect::<Vec<_>>();
+    assert_eq!(pairs.len(), keys.len());
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(dic.exact_match_search(key, key.len(), 0), values[i]);
+    }
+
+    // Keys inserted into an empty array, including prefixes of each other.
+    let mut dynamic = DynamicTrie::new();
+    for (key, value) in [("abc", 1), ("a", 2), ("ab", 3), ("b", 4), ("日本", 5)] {
+        dynamic.insert(key, value).unwrap();
+    }
+    let dic = dynamic.into_trie();
+    let results = dic.common_prefix_search("abcd", MAX_NUM_RESULTS, 0, 0);
+    assert_eq!(results.iter().map(|result| result.value).collect::<Vec<_>>(), [2, 3, 1]);
+    assert_eq!(dic.exact_match_search("日本", 0, 0), 5);
+
+    // Keys below the node for "c" shared by "ac" and "bc" only change "ac".
+    let mut units = vec![0; 256];
+    units[0] = layout::node_unit(0, false, 0x80).unwrap();
+    units[0xE1] = layout::node_unit(b'a', false, 0xE1 ^ 0x63 ^ 0x10).unwrap();
+    units[0xE2] = layout::node_unit(b'b', false, 0xE2 ^ 0x63 ^ 0x10).unwrap();
+    units[0x10] = layout::node_unit(b'c', true, 0x30).unwrap();
+    units[0x20] = layout::value_unit(7);
+    let mut dynamic = DynamicTrie::from_trie(DoubleArrayTrie::from_units(units).unwrap());
+    dynamic.insert("acd", 8).unwrap();
+    dynamic.insert("bc", 9).unwrap();
+    let dic = dynamic.into_trie();
+    assert_eq!(
+        dic.iter().collect::<Vec<_>>(),
+        vec![(b"ac".to_vec(), 7), (b"acd".to_vec(), 8), (b"bc".to_vec(), 9)]
+    );
+}
+
+#[test]
+fn dynamic_insert_into_large_array() {
+    // Every key of 3 bytes out of 103 takes more than 2^21 units, so nodes
+    // moved to the end of the array are too far from their children for
+    // offsets which are not multiples of 256.
+    let alphabet = 1..=103u8;
+    let mut builder = TrieBuilder::new();
+    for a in alphabet.clone() {
+        for b in alphabet.clone() {
+            for c in alphabet.clone() {
+                builder.insert([a, b, c], a as i32 + b as i32 + c as i32).unwrap();
+            }
+        }
+    }
+    let mut dynamic = DynamicTrie::from_trie(builder.finish().unwrap());
+    assert!(dynamic.array().len() > 1 << 21);
+
+    // New labels below nodes at the start of the array make their children,
+    // and the nodes below those, move to the end of the array.
+    let inserted = [("\x01\x02x", 1), ("\x01x", 2), ("x", 3), ("ggg\x01", 4)];
+    for (key, value) in inserted {
+        assert_eq!(dynamic.insert(key, value).unwrap(), None);
+    }
+    let dic = dynamic.into_trie();
+    assert!(DoubleArrayTrie::from_units(dic.array().to_vec()).is_ok());
+    for (key, value) in inserted.into_iter().chain([("\x01\x02\x03", 6), ("ggg", 309)]) {
+        assert_eq!(dic.exact_match_search(key, key.len(), 0), value);
+    }
+    assert_eq!(dic.iter().count(), 103 * 103 * 103 + 4);
+}
+
+#[test]
+fn perfect_hash() {
+    let data = TEST_DATA.lock().unwrap();
+    let TestData { invalid_keys, keys, .. } = &(*data);
+
+    let mut dic = DoubleArrayTrie::new();
+    dic.build(keys.len(), keys, None, None, None).unwrap();
+    let hash = PerfectHash::new(dic).unwrap();
+    assert_eq!(hash.len(), keys.len());
+    for (id, key) in keys.iter().enumerate() {
+        assert_eq!(hash.id_of(key), Some(id));
+        assert_eq!(hash.key_of(id).unwrap(), key.as_bytes());
+    }
+    for key in invalid_keys {
+        assert_eq!(hash.id_of(key), None);
+    }
+    assert_eq!(hash.key_of(keys.len()), None);
+
+    // IDs follow the order of the keys, whatever their values.
+    let mut builder = TrieBuilder::new();
+    builder.extend([("a", 5), ("ab", 9), ("abc", 1), ("b", 0)]).unwrap();
+    let hash = PerfectHash::new(builder.finish().unwrap()).unwrap();
+    assert_eq!(["a", "ab", "abc", "b", "c"].map(|key| hash.id_of(key)), [Some(0), Some(1), Some(2), Some(3), None]);
+    assert_eq!(hash.key_of(2).unwrap(), b"abc");
+    assert!(PerfectHash::new(DoubleArrayTrie::new()).unwrap().is_empty());
+
+    // The shared node of a DAWG has no single ID.
+    let mut units = vec![0; 256];
+    units[0] = layout::node_unit(0, false, 0x80).unwrap();
+    units[0xE1] = layout::node_unit(b'a', false, 0xE1 ^ 0x63 ^ 0x10).unwrap();
+    units[0xE2] = layout::node_unit(b'b', false, 0xE2 ^ 0x63 ^ 0x10).unwrap();
+    units[0x10] = layout::node_unit(b'c', true, 0x30).unwrap();
+    units[0x20] = layout::value_unit(7);
+    let dawg = DoubleArrayTrie::from_units(units).unwrap();
+    assert!(matches!(PerfectHash::new(dawg), Err(Error::InvalidFormat(_))));
+}
+
+#[test]
+fn value_range() {
+    let keys = ["apple", "banana", "cherry"].map(str::to_owned);
+
+    let mut dic = DoubleArrayTrie::new();
+    assert_eq!(dic.build(3, &keys, None, Some(&[1, -2, 3]), None), Err("negative value at index 1: -2"));
+    assert_eq!(dic.build(3, &keys, None, Some(&[1, 2]), None), Err("2 values given for 3 keys"));
+    assert_eq!(dic.build(4, &keys, None, None, None), Err("3 keys given for 4 keys"));
+    // Values of keys which are not built are not checked.
+    dic.build(2, &keys, None, Some(&[1, 2, -3]), None).unwrap();
+    assert_eq!(dic.exact_match_search("banana", 0, 0), 2);
+
+    let result = dic.build_u32(&keys, None, &[0, MAX_VALUE + 1, u32::MAX], None);
+    assert!(matches!(result, Err(Error::ValueOutOfRange { index: 1, value }) if value == MAX_VALUE + 1));
+    assert!(result.unwrap_err().to_string().ends_with("2147483648 is greater than 2147483647"));
+    let result = dic.build_u32(&keys, None, &[0, 7], None);
+    assert!(matches!(result, Err(Error::CountMismatch { what: "values", count: 2, num_keys: 3 })));
+    // Values beyond the keys are neither checked nor built.
+    dic.build_u32(&keys, None, &[0, 7, MAX_VALUE, u32::MAX], None).unwrap();
+    assert_eq!(dic.exact_match_search_u32("cherry", 0, 0), Some(MAX_VALUE));
+    assert_eq!(dic.exact_match_search_u32("apple", 0, 0), Some(0));
+    assert_eq!(dic.exact_match_search_u32("app", 0, 0), None);
+    assert_eq!(DoubleArrayTrie::new().exact_match_search_u32("apple", 0, 0), None);
+}
@@ -0,0 +1,300 @@
+//! Module for building dictionaries from text files.
+
+use std::io::BufRead;
+
+use crate::{
+    builder::BuildReport,
+    darts::DoubleArrayTrie,
+    error::{Error, Result},
+};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Lines,
+    Tsv,
+    Csv {
+        key_column: usize,
+        value_column: Option<usize>,
+    },
+}
+
+/// Layout of the records of a text input.
+///
+/// Blank lines are always skipped and line endings may be `\n` or `\r\n`.
+/// Keys without a value get their index in byte order, as with
+/// [`DoubleArrayTrie::build`] without values.
+#[derive(Clone)]
+pub struct TextFormat {
+    kind: Kind,
+    comment: Option<Vec<u8>>,
+    has_header: bool,
+}
+
+impl TextFormat {
+    /// One key per line, taken as is.
+    pub fn lines() -> TextFormat {
+        TextFormat::new(Kind::Lines)
+    }
+
+    /// A key and a value separated by a tab on each line. `\t`, `\n`, `\r`
+    /// and `\\` are unescaped in keys.
+    pub fn tsv() -> TextFormat {
+        TextFormat::new(Kind::Tsv)
+    }
+
+    /// Comma-separated values, whose key is in `key_column` and whose value,
+    /// if any, is in `value_column`, both counted from 0. Fields may be
+    /// quoted with `"`, in which case they can hold commas, line breaks and
+    /// quotes written as `""`.
+    pub fn csv(key_column: usize, value_column: Option<usize>) -> TextFormat {
+        TextFormat::new(Kind::Csv {
+            key_column,
+            value_column,
+        })
+    }
+
+    fn new(kind: Kind) -> TextFormat {
+        TextFormat {
+            kind,
+            comment: None,
+            has_header: false,
+        }
+    }
+
+    /// Skips lines starting with `prefix`, e.g. `#`.
+    pub fn comment(mut self, prefix: &str) -> TextFormat {
+        self.comment = Some(prefix.as_bytes().to_vec());
+        self
+    }
+
+    /// Skips the first record, which holds the names of the columns.
+    pub fn header(mut self, has_header: bool) -> TextFormat {
+        self.has_header = has_header;
+        self
+    }
+
+    fn has_values(&self) -> bool {
+        match self.kind {
+            Kind::Lines => false,
+            Kind::Tsv => true,
+            Kind::Csv { value_column, .. } => value_column.is_some(),
+        }
+    }
+}
+
+impl DoubleArrayTrie {
+    /// Constructs a dictionary from the records read from `reader`, in any
+    /// order. Only the first value of duplicate keys is stored.
+    ///
+    /// Malformed records, invalid keys and negative values are reported as
+    /// [`Error::Parse`] with the line where the record starts.
+    ///
+    /// ```ignore
+    /// use darts::{darts::DoubleArrayTrie, text::TextFormat};
+    ///
+    /// let mut dic = DoubleArrayTrie::new();
+    /// let file = BufReader::new(File::open("words.tsv")?);
+    /// let report = dic.build_from_reader(file, &TextFormat::tsv().comment("#"))?;
+    /// ```
+    pub fn build_from_reader<R: BufRead>(
+        &mut self,
+        reader: R,
+        format: &TextFormat,
+    ) -> Result<BuildReport> {
+        let mut pairs = read_pairs(reader, format)?;
+        // The sort is stable, so the first value of a key stays first.
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        let num_pairs = pairs.len();
+        pairs.dedup_by(|next, first| next.0 == first.0);
+
+        let keys = pairs
+            .iter()
+            .map(|(key, _)| key.as_slice())
+            .collect::<Vec<_>>();
+        let values = pairs.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+        let values = if format.has_values() {
+            Some(values.as_slice())
+        } else {
+            None
+        };
+        self.build_bytes(&keys, values, None)
+            .map_err(|what| Error::Darts(what.to_owned()))?;
+        Ok(BuildReport {
+            num_keys: keys.len(),
+            num_duplicates: num_pairs - keys.len(),
+        })
+    }
+}
+
+/// Reads lines and keeps track of their numbers.
+struct LineReader<R> {
+    reader: R,
+    line: Vec<u8>,
+    line_number: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    /// Reads the next line without its line ending, returning `false` at
+    /// the end of the input.
+    fn next_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(false);
+        }
+        self.line_number += 1;
+        if self.line.last() == Some(&b'\n') {
+            self.line.pop();
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn read_pairs<R: BufRead>(reader: R, format: &TextFormat) -> Result<Vec<(Vec<u8>, i32)>> {
+    let mut lines = LineReader {
+        reader,
+        line: Vec::new(),
+        line_number: 0,
+    };
+    let mut pairs = Vec::new();
+    let mut is_header = format.has_header;
+    while lines.next_line()? {
+        let line_number = lines.line_number;
+        if lines.line.is_empty() {
+            continue;
+        }
+        if let Some(prefix) = &format.comment {
+            if lines.line.starts_with(prefix) {
+                continue;
+            }
+        }
+        let error = |message: String| Error::Parse {
+            line: line_number,
+            message,
+        };
+
+        let (key, value) = match format.kind {
+            Kind::Lines => (lines.line.clone(), None),
+            Kind::Tsv => {
+                let tab = lines
+                    .line
+                    .iter()
+                    .rposition(|&byte| byte == b'\t')
+                    .ok_or_else(|| error("missing value".to_owned()))?;
+                let key = unescape(&lines.line[..tab]).map_err(error)?;
+                (key, Some(lines.line[tab + 1..].to_vec()))
+            }
+            Kind::Csv {
+                key_column,
+                value_column,
+            } => {
+                let mut fields = split_csv(&mut lines).map_err(error)?;
+                let mut take = |column: usize| {
+                    fields
+                        .get_mut(column)
+                        .map(std::mem::take)
+                        .ok_or_else(|| error(format!("missing column {}", column)))
+                };
+                let key = take(key_column)?;
+                let value = value_column.map(&mut take).transpose()?;
+                (key, value)
+            }
+        };
+        if is_header {
+            is_header = false;
+            continue;
+        }
+
+        let value = match value {
+            Some(value) => parse_value(&value).map_err(error)?,
+            // Replaced by the index in byte order when building.
+            None => 0,
+        };
+        if key.is_empty() || key.contains(&0) {
+            return Err(error(format!(
+                "invalid key {:?}",
+                String::from_utf8_lossy(&key)
+            )));
+        }
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+fn parse_value(value: &[u8]) -> std::result::Result<i32, String> {
+    let text = String::from_utf8_lossy(value);
+    let value = text
+        .trim()
+        .parse::<i32>()
+        .map_err(|err| format!("invalid value {:?}: {}", text, err))?;
+    if value < 0 {
+        return Err(format!("negative value: {}", value));
+    }
+    Ok(value)
+}
+
+/// Replaces the escape sequences of a TSV field.
+fn unescape(field: &[u8]) -> std::result::Result<Vec<u8>, String> {
+    let mut unescaped = Vec::with_capacity(field.len());
+    let mut bytes = field.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b't') => unescaped.push(b'\t'),
+            Some(b'n') => unescaped.push(b'\n'),
+            Some(b'r') => unescaped.push(b'\r'),
+            Some(b'\\') => unescaped.push(b'\\'),
+            Some(&other) => return Err(format!("invalid escape sequence \\{}", other as char)),
+            None => return Err("incomplete escape sequence".to_owned()),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Splits the current line into CSV fields, reading more lines while a
+/// quoted field is not closed.
+fn split_csv<R: BufRead>(lines: &mut LineReader<R>) -> std::result::Result<Vec<Vec<u8>>, String> {
+    let mut fields = vec![Vec::new()];
+    let mut in_quotes = false;
+    let mut pos = 0;
+    loop {
+        if pos == lines.line.len() {
+            if !in_quotes {
+                return Ok(fields);
+            }
+            match lines.next_line() {
+                Ok(true) => {}
+                Ok(false) => return Err("unterminated quoted field".to_owned()),
+                Err(err) => return Err(err.to_string()),
+            }
+            fields.last_mut().unwrap().push(b'\n');
+            pos = 0;
+            continue;
+        }
+
+        let byte = lines.line[pos];
+        pos += 1;
+        let field = fields.last_mut().unwrap();
+        if in_quotes {
+            if byte != b'"' {
+                field.push(byte);
+            } else if lines.line.get(pos) == Some(&b'"') {
+                field.push(b'"');
+                pos += 1;
+            } else {
+                in_quotes = false;
+            }
+        } else {
+            match byte {
+                b',' => fields.push(Vec::new()),
+                b'"' if field.is_empty() => in_quotes = true,
+                _ => field.push(byte),
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Module for decoding units of a double array.
+//!
+//! Each unit is a 32-bit integer laid out as in Darts-clone: a unit
+//! either holds a value (the most significant bit is set) or the label of
+//! the node, a flag telling whether the node has a leaf and the offset to
+//! its children.
+
+use crate::error::{Error, Result};
+
+/// Tests whether the node has a leaf child, i.e. a key ends at the node.
+pub(crate) fn has_leaf(unit: u32) -> bool {
+    (unit >> 8) & 1 == 1
+}
+
+/// Returns the value stored in a leaf unit.
+pub(crate) fn value(unit: u32) -> i32 {
+    (unit & ((1 << 31) - 1)) as i32
+}
+
+/// Returns the label of the node. Leaf units never match a label because
+/// the most significant bit is kept.
+pub(crate) fn label(unit: u32) -> u32 {
+    unit & ((1 << 31) | 0xFF)
+}
+
+/// Returns the offset from the node to its children.
+pub(crate) fn offset(unit: u32) -> u32 {
+    (unit >> 10) << ((unit & (1 << 9)) >> 6)
+}
+
+/// Returns the position of the child labeled `label` of the node at `id`,
+/// or [`None`] if there is no such child. `label` must not be 0, use
+/// [`leaf_value`] to look for the leaf instead.
+pub(crate) fn child(units: &[u32], id: usize, label: u8) -> Option<usize> {
+    let child = id ^ offset(units[id]) as usize ^ label as usize;
+    match units.get(child) {
+        Some(&unit) if self::label(unit) == label as u32 => Some(child),
+        _ => None,
+    }
+}
+
+/// Returns the value of the key ending at the node at `id`, if any.
+pub(crate) fn leaf_value(units: &[u32], id: usize) -> Option<i32> {
+    let unit = units[id];
+    if !has_leaf(unit) {
+        return None;
+    }
+    units
+        .get(id ^ offset(unit) as usize)
+        .map(|&leaf| value(leaf))
+}
+
+/// Returns the value of `key`, if it is in `units`.
+pub(crate) fn exact_value(units: &[u32], key: &[u8]) -> Option<i32> {
+    if units.is_empty() || key.is_empty() {
+        return None;
+    }
+    let mut id = 0;
+    for &label in key {
+        if label == 0 {
+            return None;
+        }
+        id = child(units, id, label)?;
+    }
+    leaf_value(units, id)
+}
+
+/// Returns the labels and positions of the children of the node at `id` in
+/// label order, without its leaf.
+pub(crate) fn children(units: &[u32], id: usize) -> impl Iterator<Item = (u8, usize)> + '_ {
+    (1..=0xFF).filter_map(move |label| child(units, id, label).map(|child| (label, child)))
+}
+
+/// Checks that searches starting from the root can not read out of `units`:
+/// the array must be made of whole blocks of 256 units, and the children of
+/// each unit which may be a node must be in the array. Units holding a value
+/// are never taken for nodes because their label can not match a byte.
+pub(crate) fn validate(units: &[u32]) -> Result<()> {
+    if units.is_empty() || !units.len().is_multiple_of(256) {
+        return Err(Error::InvalidFormat(format!(
+            "{} units are not whole blocks of 256",
+            units.len()
+        )));
+    }
+    for (id, &unit) in units.iter().enumerate() {
+        if unit >> 31 == 0 && (id ^ offset(unit) as usize) | 0xFF >= units.len() {
+            return Err(Error::InvalidFormat(format!(
+                "unit {} refers to children out of the array",
+                id
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Module for zero-copy archives, enabled by the feature `rkyv`.
+//!
+//! A dictionary is archived as its array of units, which rkyv stores in
+//! little-endian order. The archived form is searched in place: on
+//! little-endian hosts, [`ArchivedDoubleArrayTrie::as_trie`] hands the
+//! archived units to Darts-clone as a borrowed array, as
+//! [`DoubleArrayTrie::from_slice`] does.
+//!
+//! Value side tables are archived by putting the dictionary and the table in
+//! one struct deriving [`rkyv::Archive`].
+
+use rkyv::{
+    bytecheck::{CheckBytes, Verify},
+    munge::munge,
+    rancor::{Fallible, Source},
+    rend::u32_le,
+    ser::{Allocator, Writer},
+    vec::{ArchivedVec, VecResolver},
+    Archive, Deserialize, Place, Portable, Serialize,
+};
+
+use crate::{
+    darts::{DoubleArrayTrie, TrieRef},
+    header::ByteOrder,
+    unit,
+};
+
+/// Archived form of [`DoubleArrayTrie`].
+///
+/// Validating an archive, e.g. with `rkyv::access`, also checks the units as
+/// by [`DoubleArrayTrie::from_units`], so that they are searched without
+/// being checked again.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck, verify)]
+#[rkyv(crate = rkyv)]
+#[repr(C)]
+pub struct ArchivedDoubleArrayTrie {
+    units: ArchivedVec<u32_le>,
+}
+
+impl ArchivedDoubleArrayTrie {
+    /// Returns the archived units.
+    pub fn units(&self) -> &[u32_le] {
+        self.units.as_slice()
+    }
+
+    /// Returns a dictionary searching the archived units. Nothing is copied
+    /// nor checked again on little-endian hosts, while big-endian hosts get
+    /// swapped units.
+    pub fn as_trie(&self) -> TrieRef<'_> {
+        match self.native_units() {
+            Some(units) => DoubleArrayTrie::from_valid_slice(units),
+            None => DoubleArrayTrie::from_valid_units(self.to_units()).into(),
+        }
+    }
+
+    /// Returns the archived units as they are on little-endian hosts.
+    fn native_units(&self) -> Option<&[u32]> {
+        if ByteOrder::NATIVE != ByteOrder::Little {
+            return None;
+        }
+        // `u32_le` has the layout of `u32` on little-endian hosts.
+        let units = self.units();
+        Some(unsafe { std::slice::from_raw_parts(units.as_ptr() as *const u32, units.len()) })
+    }
+
+    fn to_units(&self) -> Vec<u32> {
+        self.units().iter().map(|unit| unit.to_native()).collect()
+    }
+}
+
+unsafe impl<C> Verify<C> for ArchivedDoubleArrayTrie
+where
+    C: Fallible + ?Sized,
+    C::Error: Source,
+{
+    fn verify(&self, _: &mut C) -> Result<(), C::Error> {
+        let result = match self.native_units() {
+            _ if self.units.is_empty() => Ok(()),
+            Some(units) => unit::validate(units),
+            None => unit::validate(&self.to_units()),
+        };
+        result.map_err(C::Error::new)
+    }
+}
+
+impl Archive for DoubleArrayTrie {
+    type Archived = ArchivedDoubleArrayTrie;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedDoubleArrayTrie { units } = out);
+        ArchivedVec::resolve_from_slice(self.array(), resolver, units);
+    }
+}
+
+impl<S: Fallible + Allocator + Writer + ?Sized> Serialize<S> for DoubleArrayTrie {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedVec::serialize_from_slice(self.array(), serializer)
+    }
+}
+
+impl<D> Deserialize<DoubleArrayTrie, D> for ArchivedDoubleArrayTrie
+where
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize(&self, _: &mut D) -> Result<DoubleArrayTrie, D::Error> {
+        DoubleArrayTrie::from_units(self.to_units()).map_err(D::Error::new)
+    }
+}
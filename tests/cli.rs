@@ -0,0 +1,75 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{self, Command, Output, Stdio},
+};
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("test-darts-cli-{}-{}", process::id(), name))
+}
+
+fn darts(args: &[&str]) -> Output {
+    darts_with_input(args, b"")
+}
+
+fn darts_with_input(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_darts"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn build_lookup_verify() {
+    let input = temp_path("input.tsv");
+    let dic = temp_path("dict");
+    // A key which is not UTF-8 can be stored and verified.
+    fs::write(&input, b"banana\t2\napple\t1\n\xff\xfe\t3\napp\t4\n").unwrap();
+    let (input_name, dic_name) = (input.to_str().unwrap(), dic.to_str().unwrap());
+
+    let output = stdout(&darts(&["build", input_name, dic_name]));
+    assert!(output.starts_with("keys: 4, duplicates: 0,"), "{}", output);
+    let output = stdout(&darts(&["lookup", dic_name, "apple", "grape"]));
+    assert_eq!(output, "apple\t1\ngrape\t-1\n");
+    // Queries read from the standard input may contain a null character.
+    let output = stdout(&darts_with_input(&["lookup", dic_name], b"app\napp\0le\n"));
+    assert_eq!(output, "app\t4\napp\0le\t-1\n");
+    let output = stdout(&darts(&["longest", dic_name, "apples"]));
+    assert_eq!(output, "apples\t1\t5\n");
+    assert_eq!(stdout(&darts(&["verify", dic_name])), "ok: 4 keys\n");
+    assert_eq!(
+        stdout(&darts(&["verify", dic_name, input_name])),
+        "ok: 4 keys\n"
+    );
+
+    // A value which differs from the dictionary fails the verification.
+    fs::write(&input, b"banana\t2\napple\t5\n").unwrap();
+    let output = darts(&["verify", dic_name, input_name]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("apple: expected 5, found 1"));
+
+    // Parse errors point at the line of the input.
+    fs::write(&input, b"banana\t2\napple\n").unwrap();
+    let output = darts(&["build", input_name, dic_name]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("{}:2:", input_name)), "{}", stderr);
+
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&dic).unwrap();
+}